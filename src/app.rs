@@ -0,0 +1,1100 @@
+use crate::cache::EtagCache;
+use crate::cli::{Cli, Command, FileAllocation, GetArgs};
+use crate::engine::{
+    DownloadItem, DownloadOutcome, download_file, format_listing, has_control_file,
+    overridden_aria2_options, preflight, probe_client, resolve_filename,
+};
+use crate::error::{DlrsError, exit_code, is_cancelled};
+use crate::input::{read_exclude_file, read_input_file, read_proxy_list};
+use crate::listing::{expand_directory, is_directory_url};
+use crate::logging::{Color, log_error, log_info, log_success, log_warning, paint};
+use crate::menu::ActiveDownloads;
+#[cfg(feature = "metrics")]
+use crate::metrics;
+use crate::progress::{BatchStatus, HumanBytes, MultiProgress};
+use crate::tuning::{AUTO_PARALLEL_MAX, Throughput, Tuner};
+use crate::utils::{
+    apply_rewrites, aria2_install_hint, compile_exclude, copy_on_write_fs, env_headers,
+    expand_url_pattern, find_executable, is_torrent_source, redact_url, setup_destination,
+    validate_url,
+};
+use crate::{
+    cli, config, engine, job, logging, manifest, menu, profile, progress, session, usage, verify,
+};
+use clap::error::ErrorKind;
+use clap::{CommandFactory, FromArgMatches};
+use futures::stream::{self, FuturesUnordered, StreamExt};
+use std::collections::{HashSet, VecDeque};
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+use tokio::signal;
+
+const PREFETCH_CONCURRENCY: usize = 8;
+/// Oldest aria2c with every option dlrs passes.
+const MIN_ARIA2_VERSION: &str = "1.35.0";
+/// How often `--auto-parallel` measures throughput and adjusts.
+const TUNE_INTERVAL: Duration = Duration::from_secs(5);
+/// How often the batch line refreshes its byte count and ETA.
+const STATUS_INTERVAL: Duration = Duration::from_secs(1);
+
+struct Aria2Info {
+    version: String,
+    path: Option<PathBuf>,
+}
+
+fn check_aria2c() -> anyhow::Result<Aria2Info> {
+    let output = match std::process::Command::new("aria2c")
+        .arg("--version")
+        .output()
+    {
+        Ok(o) => o,
+        Err(_) => {
+            let hint = aria2_install_hint(std::env::consts::OS, |m| find_executable(m).is_some())
+                .map(|cmd| format!("Install it with: {}", cmd))
+                .unwrap_or_else(|| {
+                    "Install it with your package manager or from https://aria2.github.io/"
+                        .to_string()
+                });
+            anyhow::bail!(
+                "aria2c not found in PATH. dlrs needs aria2c {} or newer.\n{}",
+                MIN_ARIA2_VERSION,
+                hint
+            )
+        }
+    };
+
+    // First line looks like "aria2 version 1.37.0"
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let version = stdout
+        .lines()
+        .next()
+        .and_then(|line| line.split_whitespace().last())
+        .unwrap_or("unknown")
+        .to_string();
+
+    Ok(Aria2Info {
+        version,
+        path: find_executable("aria2c"),
+    })
+}
+
+fn version_string(aria2: &anyhow::Result<Aria2Info>) -> String {
+    let aria2 = match aria2 {
+        Ok(info) => format!(
+            "aria2c {} ({})",
+            info.version,
+            info.path
+                .as_ref()
+                .map(|p| p.display().to_string())
+                .unwrap_or_else(|| "unknown path".to_string())
+        ),
+        Err(_) => "aria2c not found in PATH".to_string(),
+    };
+    format!("{}\n{}", env!("CARGO_PKG_VERSION"), aria2)
+}
+
+/// The `dlrs` command: parses the process's arguments, runs the
+/// subcommand and exits with its status.
+pub async fn run() {
+    let args = cli::with_default_subcommand(std::env::args().collect());
+    let args = if cli::is_download_command(&args) {
+        // Config file first, then the job's settings, then the command line
+        match job::args_with_job(args, 2).and_then(|args| config::args_with_config(args, 2)) {
+            Ok(args) => args,
+            Err(e) => {
+                log_error(&format!("{:?}", e));
+                std::process::exit(1);
+            }
+        }
+    } else {
+        args
+    };
+    // Running aria2c costs a process, so only --version and downloads ask
+    let mut command = Cli::command();
+    if args.iter().skip(1).any(|a| a == "--version") {
+        command = command.long_version(version_string(&check_aria2c()));
+    }
+    let matches = command.get_matches_from(args);
+    let parsed = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+
+    if let Some(shell) = parsed.completions {
+        clap_complete::generate(shell, &mut Cli::command(), "dlrs", &mut std::io::stdout());
+        return;
+    }
+
+    if let Some(Command::Verify(args)) = &parsed.command {
+        let dir = args.destination.as_deref().unwrap_or(".");
+        let result =
+            verify::verify_manifest(&args.checksums, Path::new(dir), &args.files, args.quiet);
+        if let Err(e) = result {
+            log_error(&format!("{:?}", e));
+            std::process::exit(exit_code(&e));
+        }
+        return;
+    }
+
+    let mut cli = match parsed.command {
+        Some(Command::Get(args)) => args,
+        Some(Command::Resume(resume)) => GetArgs {
+            input_file: Some(resume.session),
+            ..resume.options
+        },
+        Some(Command::Verify(_)) | None => {
+            let _ = Cli::command().print_help();
+            return;
+        }
+    };
+    if cli.urls.is_empty()
+        && cli.input_file.is_none()
+        && cli.job.is_none()
+        && cli.resume_all.is_none()
+    {
+        usage_error(
+            ErrorKind::MissingRequiredArgument,
+            "no URLs given (pass URLs, --input-file, --job or --resume-all)",
+        );
+    }
+    if let Some(dir) = &cli.resume_all {
+        cli.destination = Some(dir.clone());
+    }
+    if let Some((_, sub_matches)) = matches.subcommand() {
+        if let Err(msg) = cli::check_output_pairs(sub_matches) {
+            usage_error(ErrorKind::WrongNumberOfValues, &msg);
+        }
+        profile::apply_profile(&mut cli, sub_matches);
+    }
+    // Come first so a --header of the same name replaces them
+    let header_name = |h: &String| h.split(':').next().unwrap_or("").trim().to_lowercase();
+    let given: HashSet<String> = cli.headers.iter().map(header_name).collect();
+    // vars() would panic on a non-UTF-8 variable anywhere in the environment
+    let vars = std::env::vars_os()
+        .filter_map(|(k, v)| Some((k.into_string().ok()?, v.into_string().ok()?)));
+    let env = env_headers(vars).unwrap_or_else(|e| {
+        log_error(&format!("{:?}", e));
+        std::process::exit(1);
+    });
+    let mut headers: Vec<String> = env
+        .into_iter()
+        .filter(|h| !given.contains(&header_name(h)))
+        .collect();
+    headers.append(&mut cli.headers);
+    cli.headers = headers;
+
+    if cli.no_logs {
+        logging::disable_logs();
+    }
+
+    // --list only probes; it never starts aria2c
+    if !cli.list
+        && let Err(e) = check_aria2c()
+    {
+        log_error(&e.to_string());
+        std::process::exit(1);
+    }
+
+    let cancel_token = tokio_util::sync::CancellationToken::new();
+    let cancel_token_clone = cancel_token.clone();
+
+    tokio::spawn(async move {
+        let mut interrupts = 0;
+        while let Ok(()) = signal::ctrl_c().await {
+            interrupts += 1;
+            if interrupts == 1 {
+                eprintln!(
+                    "\n{} Received interrupt signal, cancelling downloads (Ctrl+C again to force)...",
+                    paint("[WARNING]", Color::Yellow)
+                );
+                cancel_token_clone.cancel();
+            } else {
+                // Don't leave aria2c running after dlrs itself is gone
+                engine::kill_all_aria2c();
+                eprintln!(
+                    "{} Killed aria2c without cleaning up",
+                    paint("[WARNING]", Color::Yellow)
+                );
+                std::process::exit(130);
+            }
+        }
+    });
+
+    let throughput = Arc::new(Throughput::default());
+    let target_dir = OnceLock::new();
+    let res = run_downloads(&cli, cancel_token, throughput.clone(), &target_dir).await;
+    // The last events are still queued for the frontend
+    progress::close_socket();
+    let outcomes = match res {
+        Ok(_) if cli.list => return,
+        Ok(outcomes) => outcomes,
+        Err(e) => {
+            if is_cancelled(&e) {
+                log_warning("Downloads cancelled.");
+            } else {
+                log_error(&format!("{:?}", e));
+            }
+            // What did arrive still counts
+            let transferred = throughput.total();
+            if transferred > 0 {
+                report_transferred(&cli, transferred, None);
+            }
+            // A batch that ran but had failures still gets its hook
+            if let Some(DlrsError::BatchFailed { total, errors }) = e.downcast_ref()
+                && let Some(dir) = target_dir.get()
+            {
+                after_batch(&cli, dir, *total, errors.len()).await;
+            }
+            std::process::exit(exit_code(&e));
+        }
+    };
+
+    let cancelled: Vec<&DownloadOutcome> = outcomes.iter().filter(|o| o.cancelled).collect();
+    if !cli.quiet {
+        if !cancelled.is_empty() {
+            log_success(&format!(
+                "{} of {} downloads completed, {} cancelled",
+                outcomes.len() - cancelled.len(),
+                outcomes.len(),
+                cancelled.len()
+            ));
+        } else if outcomes.len() == 1 {
+            log_success("Download completed successfully!");
+        } else {
+            log_success("All downloads completed successfully!");
+        }
+        for outcome in outcomes.iter().filter(|o| !o.skipped && o.bytes > 0) {
+            log_info(&outcome.summary());
+        }
+        for outcome in &cancelled {
+            log_warning(&format!("Cancelled: {}", outcome.filename));
+        }
+    }
+
+    let files = outcomes
+        .iter()
+        .filter(|o| !o.skipped && !o.cancelled)
+        .count();
+    report_transferred(&cli, throughput.total(), Some(files));
+    let dir = target_dir.get().map(String::as_str).unwrap_or_default();
+    if !after_batch(&cli, dir, outcomes.len(), 0).await {
+        std::process::exit(1);
+    }
+}
+
+/// Prints the bytes received this run and adds them to `--track-usage`'s
+/// total. `files` is how many were downloaded, when the batch succeeded.
+fn report_transferred(cli: &GetArgs, transferred: u64, files: Option<usize>) {
+    if !cli.quiet {
+        match files {
+            Some(files) if files > 0 => log_info(&format!(
+                "Transferred {} across {} file{}",
+                HumanBytes(transferred),
+                files,
+                if files == 1 { "" } else { "s" }
+            )),
+            Some(_) => {}
+            None => log_info(&format!("Transferred {}", HumanBytes(transferred))),
+        }
+    }
+    if cli.track_usage {
+        match usage::record_usage(transferred) {
+            Ok(total) if !cli.quiet => {
+                log_info(&format!("{} downloaded this month", HumanBytes(total)))
+            }
+            Ok(_) => {}
+            Err(e) => log_warning(&format!("Couldn't update the usage total: {:#}", e)),
+        }
+    }
+}
+
+/// Runs the `--after-batch` command, if any, with `dir` as the destination.
+/// Returns false when it failed and `--strict-hooks` makes that an error.
+async fn after_batch(cli: &GetArgs, dir: &str, total: usize, failed: usize) -> bool {
+    let Some(cmd) = &cli.after_batch else {
+        return true;
+    };
+    let status = hook_command(cmd)
+        .env("DLRS_TOTAL", total.to_string())
+        .env("DLRS_SUCCEEDED", (total - failed).to_string())
+        .env("DLRS_FAILED", failed.to_string())
+        .env("DLRS_DIR", dir)
+        .status()
+        .await;
+    let Some(problem) = hook_problem("--after-batch", status) else {
+        return true;
+    };
+    if cli.strict_hooks {
+        log_error(&problem);
+        false
+    } else {
+        log_warning(&problem);
+        true
+    }
+}
+
+/// Runs `--on-complete` for a file that finished, or that failed its
+/// checksum. `checksum_ok` is aria2c's verdict, which has been reached by
+/// now; SHA-256 and SHA-512 are hashed again here so the digest can be
+/// passed on (and so paths aria2c doesn't verify, like `--decompress`, are
+/// still checked). An error means the hook failed under `--strict-hooks`.
+async fn on_complete(
+    cli: &GetArgs,
+    item: &DownloadItem,
+    dir: &str,
+    checksum_ok: bool,
+    mp: Option<&MultiProgress>,
+) -> anyhow::Result<()> {
+    let Some(cmd) = &cli.on_complete else {
+        return Ok(());
+    };
+    let mut command = hook_command(cmd);
+    command
+        .env("DLRS_FILE", &item.file_path)
+        .env("DLRS_URL", &item.url)
+        .env("DLRS_DIR", dir);
+    if let Some(checksum) = item.options.checksum.clone() {
+        let path = PathBuf::from(&item.file_path);
+        let (ok, digest) = tokio::task::spawn_blocking(move || {
+            let expected = checksum
+                .split_once('=')
+                .map_or("", |(_, d)| d)
+                .to_ascii_lowercase();
+            match verify::checksum_digest(&path, &checksum) {
+                Some(Ok(digest)) => (digest == expected, Some(digest)),
+                Some(Err(_)) => (false, None),
+                // A type dlrs doesn't hash; having matched, it's the expected one
+                None => (checksum_ok, checksum_ok.then_some(expected)),
+            }
+        })
+        .await
+        .unwrap_or((false, None));
+        command.env("DLRS_CHECKSUM_OK", ok.to_string());
+        if let Some(digest) = digest {
+            command.env("DLRS_CHECKSUM", digest);
+        }
+    }
+    let status = command.status().await;
+    match hook_problem("--on-complete", status) {
+        Some(problem) if cli.strict_hooks => Err(anyhow::anyhow!(problem)),
+        Some(problem) => {
+            logging::log_warning_with(mp, &problem);
+            Ok(())
+        }
+        None => Ok(()),
+    }
+}
+
+/// `cmd` run through the platform shell.
+fn hook_command(cmd: &str) -> tokio::process::Command {
+    let (shell, flag) = if cfg!(windows) {
+        ("cmd", "/C")
+    } else {
+        ("sh", "-c")
+    };
+    let mut command = tokio::process::Command::new(shell);
+    command.arg(flag).arg(cmd);
+    command
+}
+
+/// What went wrong running a hook, if anything.
+fn hook_problem(name: &str, status: std::io::Result<std::process::ExitStatus>) -> Option<String> {
+    match status {
+        Ok(s) if s.success() => None,
+        Ok(s) => Some(format!("{} command failed ({})", name, s)),
+        Err(e) => Some(format!("Failed to run {} command: {}", name, e)),
+    }
+}
+
+/// Exits with a clap usage error for the `get` subcommand.
+fn usage_error(kind: ErrorKind, msg: &str) -> ! {
+    let mut command = Cli::command();
+    command.build();
+    command
+        .find_subcommand_mut("get")
+        .expect("get subcommand")
+        .error(kind, msg)
+        .exit()
+}
+
+/// Runs the batch, adding what it receives to `throughput` as it goes, so
+/// the total is known however the batch ends. The destination goes in
+/// `target_dir_out` once it's set up, for `--after-batch`.
+async fn run_downloads(
+    cli: &GetArgs,
+    cancel_token: tokio_util::sync::CancellationToken,
+    throughput: Arc<Throughput>,
+    target_dir_out: &OnceLock<String>,
+) -> anyhow::Result<Vec<DownloadOutcome>> {
+    let mut downloads = cli
+        .urls
+        .iter()
+        .enumerate()
+        .map(|(i, u)| {
+            let mut item = DownloadItem::new(u.clone());
+            item.options.out = cli.outputs.get(i).cloned();
+            item
+        })
+        .collect::<Vec<_>>();
+    if let Some(path) = &cli.input_file {
+        downloads.extend(read_input_file(path)?);
+    }
+    if let Some(path) = &cli.job {
+        downloads.extend(job::read_job_downloads(path)?);
+    }
+    if let Some(dir) = &cli.resume_all {
+        let (found, orphans) = session::unfinished(dir, cli)?;
+        if !cli.quiet {
+            for name in &orphans {
+                log_warning(&format!("{}: no URL recorded for it, can't resume", name));
+            }
+        }
+        if found.is_empty() && downloads.is_empty() {
+            anyhow::bail!("No unfinished downloads to resume in '{}'", dir);
+        }
+        if !cli.quiet {
+            log_info(&format!(
+                "Resuming {} unfinished download{} in {}",
+                found.len(),
+                if found.len() == 1 { "" } else { "s" },
+                dir
+            ));
+        }
+        downloads.extend(found);
+    }
+    if downloads.is_empty() {
+        anyhow::bail!("No URLs to download");
+    }
+
+    if !cli.no_glob {
+        let mut expanded = Vec::new();
+        for item in downloads {
+            let urls = expand_url_pattern(item.url.trim())?;
+            if urls.len() > 1 && item.options.out.is_some() {
+                anyhow::bail!(
+                    "{} expands to {} URLs but has a single output name; use --no-glob or drop the name",
+                    redact_url(&item.url),
+                    urls.len()
+                );
+            }
+            for url in urls {
+                let mut copy = DownloadItem::new(url);
+                copy.options = item.options.clone();
+                expanded.push(copy);
+            }
+        }
+        downloads = expanded;
+    }
+
+    for item in &mut downloads {
+        if !cli.rewrite.is_empty() {
+            let rewritten = apply_rewrites(item.url.trim(), &cli.rewrite);
+            if rewritten != item.url.trim() && !cli.quiet {
+                log_info(&format!(
+                    "Rewrote {} -> {}",
+                    redact_url(item.url.trim()),
+                    redact_url(&rewritten)
+                ));
+            }
+            item.url = rewritten;
+        }
+        item.url = validate_url(&item.url)?;
+        cli.check_scheme(&item.url)?;
+        for mirror in &mut item.options.mirrors {
+            *mirror = validate_url(mirror)?;
+            cli.check_scheme(mirror)?;
+        }
+        let flag = if cli.range.is_some() {
+            Some("--range")
+        } else if cli.decompress {
+            Some("--decompress")
+        } else if cli.chunked.is_some() {
+            Some("--chunked")
+        } else {
+            None
+        };
+        if let Some(flag) = flag
+            && !item.url.starts_with("http://")
+            && !item.url.starts_with("https://")
+        {
+            anyhow::bail!(
+                "{} only works with HTTP(S) URLs, not {}",
+                flag,
+                redact_url(&item.url)
+            );
+        }
+    }
+
+    if cli.recursive {
+        let mut expanded = Vec::new();
+        for item in downloads {
+            if !is_directory_url(&item.url) {
+                expanded.push(item);
+                continue;
+            }
+            if item.options.out.is_some()
+                || item.options.checksum.is_some()
+                || !item.options.mirrors.is_empty()
+            {
+                anyhow::bail!(
+                    "{} is a directory for --recursive; out, checksum and mirror name a single file, so they can't apply to the files in it",
+                    redact_url(&item.url)
+                );
+            }
+            let urls = expand_directory(&item.url, cli).await?;
+            if urls.is_empty() && !cli.quiet {
+                log_warning(&format!("No files found under {}", redact_url(&item.url)));
+            }
+            for url in urls {
+                let mut file = DownloadItem::new(url);
+                file.options = item.options.clone();
+                expanded.push(file);
+            }
+        }
+        downloads = expanded;
+        if downloads.is_empty() {
+            anyhow::bail!("No files to download");
+        }
+    }
+
+    let mut excludes = cli.exclude.clone();
+    if let Some(path) = &cli.exclude_file {
+        excludes.extend(read_exclude_file(path)?);
+    }
+    if !excludes.is_empty() {
+        let patterns = excludes
+            .iter()
+            .map(|p| compile_exclude(p))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let before = downloads.len();
+        downloads.retain(|item| !patterns.iter().any(|p| p.is_match(&item.url)));
+        let excluded = before - downloads.len();
+        if excluded > 0 && !cli.quiet {
+            log_info(&format!("Excluded {} of {} URL(s)", excluded, before));
+        }
+        if downloads.is_empty() {
+            anyhow::bail!("Every URL was excluded; nothing to download");
+        }
+    }
+
+    if let Some(path) = &cli.proxy_list {
+        let (proxies, skipped) = read_proxy_list(path)?;
+        if !cli.quiet {
+            for line in &skipped {
+                log_warning(&format!("Skipping invalid proxy in {}: {}", path, line));
+            }
+        }
+        if proxies.is_empty() {
+            anyhow::bail!("No usable proxies in '{}'", path);
+        }
+        for (item, proxy) in downloads.iter_mut().zip(proxies.iter().cycle()) {
+            item.options.proxy = Some(proxy.clone());
+        }
+    }
+
+    if cli.has_torrent_options() && !cli.quiet {
+        for item in downloads.iter().filter(|i| !is_torrent_source(&i.url)) {
+            log_warning(&format!(
+                "Ignoring torrent options for non-torrent URL: {}",
+                redact_url(&item.url)
+            ));
+        }
+    }
+    let mut total = downloads.len();
+
+    if cli.list {
+        let target_dir = cli.destination.clone().unwrap_or(".".to_string());
+        let results = stream::iter(downloads.iter_mut())
+            .map(|item| async {
+                let url = item.url.clone();
+                resolve_filename(item, &target_dir, cli)
+                    .await
+                    .map_err(|e| (url, e))
+            })
+            .buffer_unordered(PREFETCH_CONCURRENCY)
+            .collect::<Vec<_>>()
+            .await;
+        for (url, e) in results.into_iter().filter_map(Result::err) {
+            log_warning(&format!("{}: {:#}", redact_url(&url), e));
+        }
+        print!("{}", format_listing(&downloads));
+        return Ok(Vec::new());
+    }
+
+    let pinned = engine::pinned_aria2_overrides(cli);
+    if !pinned.is_empty() {
+        anyhow::bail!(
+            "-X can't set {}: dlrs relies on them to find the file (use -d, -o or --overwrite-policy)",
+            pinned.join(", ")
+        );
+    }
+    if !cli.quiet {
+        let overridden = overridden_aria2_options(&downloads[0], cli);
+        if !overridden.is_empty() {
+            log_warning(&format!(
+                "-X replaces options dlrs sets itself: {}",
+                overridden.join(", ")
+            ));
+        }
+    }
+
+    // Surface unreadable certificate files before any download starts
+    probe_client(cli)?;
+    if let Some(conf) = &cli.aria2_conf
+        && !Path::new(conf).is_file()
+    {
+        anyhow::bail!("aria2c config file '{}' not found", conf);
+    }
+    #[cfg(feature = "metrics")]
+    if let Some(addr) = &cli.metrics_addr {
+        metrics::serve(addr).await?;
+    }
+    if let Some(path) = &cli.progress_socket {
+        progress::connect_socket(path)?;
+    } else if cli.machine_progress() {
+        progress::check_progress_fd(cli.progress_fd)?;
+    }
+    if cli.insecure && !cli.quiet {
+        log_warning("TLS certificate verification is disabled (--insecure)");
+    }
+    if cli.preflight {
+        let target = match &cli.preflight_url {
+            Some(url) => Some((url.as_str(), None)),
+            None => downloads
+                .iter()
+                .find(|i| i.url.starts_with("http://") || i.url.starts_with("https://"))
+                .map(|i| (i.url.as_str(), i.options.proxy.as_deref())),
+        };
+        let Some((url, proxy)) = target else {
+            anyhow::bail!(
+                "--preflight needs an HTTP(S) URL to check; pass one with --preflight-url"
+            );
+        };
+        let latency = preflight(url, cli, proxy).await?;
+        if !cli.quiet {
+            log_info(&format!(
+                "Preflight: {} answered in {} ms",
+                redact_url(url),
+                latency.as_millis()
+            ));
+        }
+    }
+
+    let target_dir = setup_destination(cli.destination.as_ref(), !cli.no_write_check)?;
+    if let Some(dir) = &cli.temp_dir {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| anyhow::anyhow!("Failed to create temp dir '{}': {}", dir, e))?;
+    }
+    let target_dir_str = target_dir.to_string_lossy().to_string();
+    let _ = target_dir_out.set(target_dir_str.clone());
+    let cache = if cli.cache {
+        Some(Arc::new(EtagCache::load(&target_dir)?))
+    } else {
+        None
+    };
+
+    if cli.prefetch_names {
+        stream::iter(downloads.iter_mut())
+            .for_each_concurrent(PREFETCH_CONCURRENCY, |item| async {
+                // A failed item keeps an empty name, so its download
+                // resolves again and reports the error itself
+                let _ = resolve_filename(item, &target_dir_str, cli).await;
+            })
+            .await;
+
+        if !cli.quiet {
+            let mut seen = HashSet::new();
+            for item in &downloads {
+                if !item.filename.is_empty() && !seen.insert(&item.filename) {
+                    log_warning(&format!(
+                        "Multiple URLs resolve to '{}'; they will overwrite each other",
+                        item.filename
+                    ));
+                }
+            }
+
+            for item in downloads.iter().filter(|i| !i.filename.is_empty()) {
+                let state = if has_control_file(item, &target_dir_str, cli) {
+                    "resuming"
+                } else {
+                    "new"
+                };
+                log_info(&format!("{}: {}", item.filename, state));
+            }
+        }
+    }
+
+    if !cli.quiet {
+        if total == 1 {
+            log_info("Starting download...");
+        } else {
+            log_info(&format!("Starting batch download of {} files...", total));
+        }
+    }
+
+    if let Some(log) = &cli.log_file
+        && !cli.quiet
+    {
+        log_info(&format!("Writing aria2c debug log to {}", log));
+    }
+
+    #[cfg(feature = "tui")]
+    let mp = if !cli.quiet && !cli.plain_progress() && !cli.machine_progress() {
+        Some(MultiProgress::new())
+    } else {
+        None
+    };
+    #[cfg(not(feature = "tui"))]
+    let mp: Option<MultiProgress> = None;
+
+    let mut cli = cli.clone();
+    // aria2c allocates in the temp dir when there is one
+    let work_dir = cli
+        .temp_dir
+        .clone()
+        .unwrap_or_else(|| target_dir_str.clone());
+    if cli.file_allocation.is_none()
+        && let Some(fs) = copy_on_write_fs(Path::new(&work_dir))
+    {
+        cli.file_allocation = Some(FileAllocation::None);
+        if !cli.quiet {
+            log_info(&format!(
+                "{} is on {} (copy-on-write), using --file-allocation none",
+                work_dir, fs
+            ));
+        }
+    }
+    if cli.auto_parallel {
+        cli.parallel_downloads = AUTO_PARALLEL_MAX;
+    }
+    // No more downloads can run at once than there are files, though a
+    // manifest may still add more
+    if cli.follow_manifest.is_none() {
+        cli.parallel_downloads = cli.parallel_downloads.clamp(1, total);
+    }
+    cli.parallel_downloads = cli.parallel_downloads.max(1);
+    if let Some(budget) = cli.max_total_connections {
+        // Every download needs at least one connection
+        if cli.parallel_downloads > budget as usize {
+            cli.parallel_downloads = budget as usize;
+        }
+        if !cli.quiet
+            && let Some(share) = engine::connection_share(&cli)
+        {
+            log_info(&format!(
+                "Connection budget {}: up to {} per download across {} parallel",
+                budget, share, cli.parallel_downloads
+            ));
+        }
+    }
+    let mut tuner = cli
+        .auto_parallel
+        .then(|| Tuner::new(cli.parallel_downloads));
+    let cli = Arc::new(cli);
+    let target_dir_str = Arc::new(target_dir_str);
+    let mp = Arc::new(mp);
+
+    let status = match mp.as_ref() {
+        Some(mp) if total > 1 || cli.follow_manifest.is_some() => {
+            // Only known with --prefetch-names; a torrent's probe sized the
+            // .torrent file, not its contents
+            let expected_bytes = downloads.iter().map(expected_size).sum::<Option<u64>>();
+            Some(Arc::new(BatchStatus::new(mp, total, expected_bytes)?))
+        }
+        _ => None,
+    };
+
+    let active = if cli.cancel_menu {
+        if std::io::stdin().is_terminal() {
+            let active = Arc::new(ActiveDownloads::default());
+            menu::spawn_cancel_menu(active.clone(), mp.clone());
+            if !cli.quiet {
+                log_info("Press Enter to cancel a single download");
+            }
+            Some(active)
+        } else {
+            log_warning("Ignoring --cancel-menu: stdin is not a terminal");
+            None
+        }
+    } else {
+        None
+    };
+
+    let start = |mut item: DownloadItem| {
+        let cli = cli.clone();
+        let target_dir_str = target_dir_str.clone();
+        let mp = mp.clone();
+        let cancel_token = cancel_token.clone();
+        let active = active.clone();
+        let status = status.clone();
+        let cache = cache.clone();
+        let throughput = throughput.clone();
+
+        async move {
+            if let Some(status) = &status {
+                status.started();
+            }
+            #[cfg(feature = "metrics")]
+            metrics::download_started();
+
+            // The menu cancels just this download through its own child token
+            let item_token = cancel_token.child_token();
+            let entry = active.as_ref().map(|a| {
+                let label = if item.filename.is_empty() {
+                    redact_url(&item.url)
+                } else {
+                    item.filename.clone()
+                };
+                a.add(label, item_token.clone())
+            });
+
+            let size = expected_size(&item).unwrap_or(0);
+
+            // Removed outer tokio::select! to ensure download_file handles cleanup logic
+            let res = download_file(
+                &mut item,
+                &target_dir_str,
+                &cli,
+                mp.as_ref().as_ref(),
+                cache.as_deref(),
+                Some(&throughput),
+                None,
+                item_token,
+            )
+            .await;
+            if let (Some(active), Some(id)) = (&active, entry) {
+                active.remove(id);
+            }
+
+            if let Some(status) = &status {
+                let received = res.as_ref().map_or(size, |o| o.transferred);
+                status.finished(size.saturating_sub(received));
+            }
+            let res = match res {
+                Ok(outcome) if !outcome.skipped => {
+                    on_complete(&cli, &item, &target_dir_str, true, mp.as_ref().as_ref())
+                        .await
+                        .map(|()| outcome)
+                }
+                Err(e)
+                    if matches!(
+                        e.downcast_ref::<DlrsError>(),
+                        Some(DlrsError::ChecksumMismatch)
+                    ) =>
+                {
+                    // The hook's failure can't make this any more failed
+                    let _ = on_complete(&cli, &item, &target_dir_str, false, mp.as_ref().as_ref())
+                        .await;
+                    Err(e)
+                }
+                res => res,
+            };
+            #[cfg(feature = "metrics")]
+            metrics::download_finished(match &res {
+                Ok(_) => metrics::Ending::Completed,
+                Err(e) if is_cancelled(e) => metrics::Ending::Cancelled,
+                Err(_) => metrics::Ending::Failed,
+            });
+            cancelled_from_menu(res, &item, &cancel_token)
+                .map_err(|e| e.context(format!("Failed: {}", redact_url(&item.url))))
+        }
+    };
+
+    let mut limit = tuner.as_ref().map_or(cli.parallel_downloads, Tuner::limit);
+    let mut running = FuturesUnordered::new();
+    let mut outcomes = Vec::new();
+    let mut errors = Vec::new();
+    let mut window_errors = 0;
+    let mut queue = VecDeque::from(downloads);
+    let mut follow_manifest = cli.follow_manifest.clone();
+    let mut tune_tick = tokio::time::interval(TUNE_INTERVAL);
+    tune_tick.tick().await;
+    let mut status_tick = tokio::time::interval(STATUS_INTERVAL);
+
+    // --ramp-up spaces out starts, whatever the limit; the first goes at once
+    let start_spacing = cli.ramp_up.map(|rate| Duration::from_secs_f64(1.0 / rate));
+    let mut next_start = tokio::time::Instant::now();
+
+    loop {
+        while running.len() < limit
+            && (start_spacing.is_none() || tokio::time::Instant::now() >= next_start)
+            && let Some(item) = queue.pop_front()
+        {
+            running.push(start(item));
+            if let Some(spacing) = start_spacing {
+                next_start = next_start.max(tokio::time::Instant::now()) + spacing;
+            }
+        }
+        let ramping = start_spacing.is_some() && running.len() < limit && !queue.is_empty();
+
+        tokio::select! {
+            // Nothing running only means done once the queue is empty too
+            res = running.next(), if !running.is_empty() || queue.is_empty() => {
+                let Some(res) = res else {
+                    // Everything so far is done; a manifest adds a second round
+                    if let Some(path) = follow_manifest.take() {
+                        let found = manifest_downloads(&outcomes, &path, &cli);
+                        if !found.is_empty() {
+                            total += found.len();
+                            if let Some(status) = &status {
+                                status.add_total(found.len());
+                            }
+                            queue.extend(found);
+                            continue;
+                        }
+                    }
+                    break;
+                };
+                match res {
+                    Ok(outcome) => outcomes.push(outcome),
+                    Err(e) if is_cancelled(&e) && cancel_token.is_cancelled() => {
+                        // The others were cancelled too; let them stop and
+                        // count what they received
+                        while running.next().await.is_some() {}
+                        return Err(DlrsError::Cancelled.into());
+                    }
+                    Err(e) => {
+                        errors.push(e);
+                        window_errors += 1;
+                    }
+                }
+            }
+            _ = tokio::time::sleep_until(next_start), if ramping => {}
+            _ = tune_tick.tick(), if tuner.is_some() => {
+                if let Some(tuner) = &mut tuner {
+                    let rate = throughput.take() as f64 / TUNE_INTERVAL.as_secs_f64();
+                    let next = tuner.adjust(rate, window_errors);
+                    window_errors = 0;
+                    if next != limit
+                        && let Some(mp) = mp.as_ref()
+                    {
+                        let _ = mp.println(format!("Parallel downloads: {} -> {}", limit, next));
+                    }
+                    limit = next;
+                }
+            }
+            _ = status_tick.tick(), if status.is_some() => {
+                if let Some(status) = &status {
+                    status.update_bytes(throughput.total());
+                }
+            }
+        }
+    }
+
+    if !errors.is_empty() {
+        return Err(DlrsError::BatchFailed { total, errors }.into());
+    }
+
+    Ok(outcomes)
+}
+
+/// A download cancelled on its own from `--cancel-menu`, as an outcome
+/// rather than a failure: the user chose to stop it. Cancelling the whole
+/// `batch` is still an error.
+fn cancelled_from_menu(
+    res: anyhow::Result<DownloadOutcome>,
+    item: &DownloadItem,
+    batch: &tokio_util::sync::CancellationToken,
+) -> anyhow::Result<DownloadOutcome> {
+    match res {
+        Err(e) if is_cancelled(&e) && !batch.is_cancelled() => Ok(DownloadOutcome {
+            filename: if item.filename.is_empty() {
+                redact_url(&item.url)
+            } else {
+                item.filename.clone()
+            },
+            url: item.url.clone(),
+            cancelled: true,
+            ..DownloadOutcome::default()
+        }),
+        res => res,
+    }
+}
+
+/// The size the probe reported for `item`, if it's meaningful.
+fn expected_size(item: &DownloadItem) -> Option<u64> {
+    if is_torrent_source(&item.url) {
+        return None;
+    }
+    item.remote.as_ref().and_then(|r| r.content_length)
+}
+
+/// The downloads listed at `path` in the finished `--follow-manifest`
+/// manifests, with relative URLs resolved against the manifest's own.
+/// Downloads that aren't served as or named JSON are passed over; files
+/// that don't parse, and URLs that don't validate, are skipped with a
+/// warning.
+fn manifest_downloads(
+    manifests: &[DownloadOutcome],
+    path: &str,
+    cli: &GetArgs,
+) -> Vec<DownloadItem> {
+    let mut seen = HashSet::new();
+    let mut found = Vec::new();
+    for manifest in manifests {
+        if !manifest::is_json(&manifest.file_path, manifest.content_type.as_deref()) {
+            continue;
+        }
+        let file = Path::new(&manifest.file_path);
+        let urls = match manifest::read_manifest(file, path, &manifest.url) {
+            Ok(urls) => urls,
+            Err(e) => {
+                if !cli.quiet {
+                    log_warning(&format!("{:#}; not following it", e));
+                }
+                continue;
+            }
+        };
+        if urls.is_empty() && !cli.quiet {
+            log_warning(&format!("No URLs at '{}' in {}", path, manifest.filename));
+        }
+        for url in urls {
+            let checked = validate_url(&url).and_then(|url| {
+                cli.check_scheme(&url)?;
+                Ok(url)
+            });
+            match checked {
+                Ok(url) => {
+                    if seen.insert(url.clone()) {
+                        found.push(DownloadItem::new(url));
+                    }
+                }
+                Err(e) if !cli.quiet => {
+                    log_warning(&format!("{}: {:#}; skipping it", manifest.filename, e))
+                }
+                Err(_) => {}
+            }
+        }
+    }
+    if !found.is_empty() && !cli.quiet {
+        log_info(&format!(
+            "Following manifests: {} more file(s)",
+            found.len()
+        ));
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio_util::sync::CancellationToken;
+
+    #[test]
+    fn test_cancelled_from_menu() {
+        let mut item = DownloadItem::new("https://example.com/a.iso".to_string());
+        item.filename = "a.iso".to_string();
+        let cancelled = || Err(DlrsError::Cancelled.into());
+
+        // One download stopped from the menu; the batch goes on
+        let batch = CancellationToken::new();
+        let outcome = cancelled_from_menu(cancelled(), &item, &batch).unwrap();
+        assert!(outcome.cancelled && !outcome.skipped);
+        assert_eq!(outcome.filename, "a.iso");
+        assert!(cancelled_from_menu(Err(DlrsError::Network.into()), &item, &batch).is_err());
+
+        // Ctrl+C stops everything, and that's still reported as cancelled
+        batch.cancel();
+        let err = cancelled_from_menu(cancelled(), &item, &batch).unwrap_err();
+        assert!(is_cancelled(&err));
+    }
+}
@@ -0,0 +1,93 @@
+use crate::engine::DownloadItem;
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+const CACHE_FILE: &str = ".dlrs-etags.json";
+
+/// URL to last-seen ETag map, kept as `.dlrs-etags.json` in the destination.
+pub struct EtagCache {
+    path: PathBuf,
+    entries: Mutex<HashMap<String, String>>,
+}
+
+impl EtagCache {
+    pub fn load(dir: &Path) -> Result<Self> {
+        let path = dir.join(CACHE_FILE);
+        let entries = match std::fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str(&contents)
+                .context(format!("Invalid ETag cache '{}'", path.display()))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => {
+                return Err(e).context(format!("Failed to read ETag cache '{}'", path.display()));
+            }
+        };
+        Ok(EtagCache {
+            path,
+            entries: Mutex::new(entries),
+        })
+    }
+
+    /// Whether the server still reports the ETag recorded for this URL.
+    /// Entries whose local file has gone missing are dropped.
+    pub fn is_fresh(&self, item: &DownloadItem) -> bool {
+        let mut entries = self.entries.lock().unwrap();
+        if !Path::new(&item.file_path).exists() {
+            entries.remove(&item.url);
+            return false;
+        }
+        let current = item.remote.as_ref().and_then(|r| r.etag.as_ref());
+        current.is_some() && entries.get(&item.url) == current
+    }
+
+    /// Records the ETag of a finished download and writes the cache out.
+    pub fn record(&self, item: &DownloadItem) -> Result<()> {
+        let Some(etag) = item.remote.as_ref().and_then(|r| r.etag.clone()) else {
+            return Ok(());
+        };
+        let mut entries = self.entries.lock().unwrap();
+        entries.insert(item.url.clone(), etag);
+        let contents = serde_json::to_string_pretty(&*entries)?;
+        std::fs::write(&self.path, contents).context(format!(
+            "Failed to write ETag cache '{}'",
+            self.path.display()
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::RemoteInfo;
+    use crate::test_util::test_dir;
+
+    #[test]
+    fn test_etag_cache() {
+        let dir = test_dir("etags");
+
+        let mut item = DownloadItem::new("https://example.com/a.bin".to_string());
+        item.file_path = dir.join("a.bin").to_string_lossy().to_string();
+        item.remote = Some(RemoteInfo {
+            etag: Some("\"v1\"".to_string()),
+            ..RemoteInfo::default()
+        });
+        std::fs::write(&item.file_path, b"data").unwrap();
+
+        let cache = EtagCache::load(&dir).unwrap();
+        assert!(!cache.is_fresh(&item));
+        cache.record(&item).unwrap();
+
+        let cache = EtagCache::load(&dir).unwrap();
+        assert!(cache.is_fresh(&item));
+
+        item.remote.as_mut().unwrap().etag = Some("\"v2\"".to_string());
+        assert!(!cache.is_fresh(&item));
+
+        item.remote.as_mut().unwrap().etag = Some("\"v1\"".to_string());
+        std::fs::remove_file(&item.file_path).unwrap();
+        assert!(!cache.is_fresh(&item));
+        std::fs::write(&item.file_path, b"data").unwrap();
+        assert!(!cache.is_fresh(&item));
+    }
+}
@@ -1,25 +1,229 @@
-use clap::Parser;
+use crate::error::DlrsError;
+use crate::profile::Profile;
+use crate::schedule::{SpeedSchedule, parse_speed_schedule};
+use crate::utils::{
+    ByteRange, Rewrite, normalize_size, parse_aria2_option, parse_byte_range, parse_exclude,
+    parse_header, parse_rewrite, parse_size, redact_url, validate_url,
+};
+use clap::{ArgMatches, Args, Parser, Subcommand, ValueEnum};
+use clap_complete::Shell;
+use std::io::IsTerminal;
 
-#[derive(Parser, Debug, Clone)]
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProgressMode {
+    Auto,
+    Always,
+    Plain,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverwritePolicy {
+    /// Download again over the existing file
+    Overwrite,
+    /// Keep the existing file and skip the download
+    Skip,
+    /// Save the new download as name.1.ext, name.2.ext, ...
+    Rename,
+    /// Move the existing file to name.bak first
+    Backup,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProgressFormat {
+    Human,
+    Machine,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Naming {
+    /// Content-Disposition, falling back to the URL
+    Header,
+    /// Always the last segment of the URL as given
+    Url,
+    /// --name-template, filled in from the header-preferred name
+    Template,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileAllocation {
+    None,
+    Prealloc,
+    Trunc,
+    Falloc,
+}
+
+impl FileAllocation {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            FileAllocation::None => "none",
+            FileAllocation::Prealloc => "prealloc",
+            FileAllocation::Trunc => "trunc",
+            FileAllocation::Falloc => "falloc",
+        }
+    }
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scheme {
+    Http,
+    Https,
+    Ftp,
+    Magnet,
+}
+
+impl Scheme {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Scheme::Http => "http",
+            Scheme::Https => "https",
+            Scheme::Ftp => "ftp",
+            Scheme::Magnet => "magnet",
+        }
+    }
+}
+
+fn parse_chunk_size(raw: &str) -> anyhow::Result<u64> {
+    match parse_size(raw)? {
+        0 => anyhow::bail!("chunk size must be greater than 0"),
+        size => Ok(size),
+    }
+}
+
+fn parse_start_rate(raw: &str) -> anyhow::Result<f64> {
+    match raw.trim().parse::<f64>() {
+        Ok(rate) if rate.is_finite() && rate > 0.0 => Ok(rate),
+        _ => anyhow::bail!("expected a number of downloads per second greater than 0"),
+    }
+}
+
+/// Subcommands, plus the top-level flags that don't imply `get`.
+const SUBCOMMANDS: &[&str] = &["get", "resume", "verify", "help"];
+const TOP_LEVEL_FLAGS: &[&str] = &["-h", "--help", "-V", "--version", "--completions"];
+
+#[derive(Parser, Debug)]
 #[command(
     name = "dlrs",
-    version = "1.0",
+    version = env!("CARGO_PKG_VERSION"),
     about = "High-performance basic download tool powered by aria2c",
-    long_about = "dlrs is a basic wrapper around aria2c that provides optimized defaults and a modern CLI experience."
+    long_about = "dlrs is a basic wrapper around aria2c that provides optimized defaults and a modern CLI experience.",
+    args_conflicts_with_subcommands = true
 )]
 pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// Print a shell completion script and exit
+    #[arg(long, value_name = "SHELL")]
+    pub completions: Option<Shell>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Download files (the default when no subcommand is given)
+    Get(GetArgs),
+    /// Resume the downloads listed in a saved session (input file) and retry
+    /// any that are unfinished
+    Resume(ResumeArgs),
+    /// Check downloaded files against a checksums manifest without downloading
+    Verify(VerifyArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct VerifyArgs {
+    /// Manifest of "<digest>  <filename>" lines (e.g. SHA256SUMS)
+    #[arg(long)]
+    pub checksums: String,
+
+    /// Directory holding the files [default: current directory]
+    #[arg(short = 'd', long)]
+    pub destination: Option<String>,
+
+    /// Only print failed and missing files
+    #[arg(long, short = 'q')]
+    pub quiet: bool,
+
+    /// Files to check [default: every file in the manifest]
+    pub files: Vec<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct ResumeArgs {
+    /// Session file, in the same format as --input-file
+    #[arg(conflicts_with = "input_file")]
+    pub session: String,
+
+    #[command(flatten)]
+    pub options: GetArgs,
+}
+
+#[derive(Parser, Debug, Clone)]
+#[command(args_override_self = true)]
+pub struct GetArgs {
     /// Target directory for downloads
     #[arg(short = 'd', long)]
     pub destination: Option<String>,
 
     /// Maximum download speed (e.g., 1M, 500K)
-    #[arg(long = "max-speed")]
+    #[arg(long = "max-speed", value_parser = normalize_size)]
     pub max_speed: Option<String>,
 
+    /// Total speed cap shared by all parallel downloads (e.g., 4M)
+    #[arg(long = "max-overall-speed", value_parser = parse_size)]
+    pub max_overall_speed: Option<u64>,
+
+    /// Abort a download whose speed drops below this rate (e.g., 10K)
+    #[arg(long = "min-speed", value_parser = normalize_size)]
+    pub min_speed: Option<String>,
+
+    /// Speed limits by time of day (e.g., "09:00-18:00=1M, 18:00-09:00=0")
+    #[arg(long = "speed-schedule", value_parser = parse_speed_schedule)]
+    pub speed_schedule: Option<SpeedSchedule>,
+
+    /// Preset for several tuning options; explicit flags still win
+    #[arg(long, value_enum)]
+    pub profile: Option<Profile>,
+
+    /// Maximum connections per server [default: 16]
+    #[arg(long)]
+    pub connections: Option<u32>,
+
+    /// Number of pieces to split each download into [default: 32]
+    #[arg(long)]
+    pub split: Option<u32>,
+
+    /// Connections shared by all parallel downloads; caps each download's
+    /// --connections and --split at its share
+    #[arg(long = "max-total-connections", value_parser = clap::value_parser!(u32).range(1..))]
+    pub max_total_connections: Option<u32>,
+
+    /// Files smaller than this use one connection and one piece (0 disables)
+    #[arg(long = "multi-threshold", value_parser = parse_size, default_value = "10M")]
+    pub multi_threshold: u64,
+
+    /// Minimum size of each piece (e.g., 1M) [default: 1M]
+    #[arg(long = "min-split-size", value_parser = normalize_size)]
+    pub min_split_size: Option<String>,
+
+    /// aria2c disk cache size, 0 to disable [default: 128M]
+    #[arg(long = "disk-cache", value_parser = normalize_size)]
+    pub disk_cache: Option<String>,
+
+    /// File allocation method [default: falloc]
+    #[arg(long = "file-allocation", value_enum)]
+    pub file_allocation: Option<FileAllocation>,
+
     /// Download timeout in seconds
     #[arg(long, default_value_t = 60)]
     pub timeout: u64,
 
+    /// Seconds between aria2c's progress summaries (0 turns them off)
+    #[arg(long = "summary-interval", value_name = "SECS", default_value_t = 1)]
+    pub summary_interval: u64,
+
+    /// Give up on a file that hasn't finished this many seconds after it started
+    #[arg(long = "per-file-deadline", value_name = "SECS", value_parser = clap::value_parser!(u64).range(1..))]
+    pub per_file_deadline: Option<u64>,
+
     /// Connection timeout in seconds
     #[arg(long = "connect-timeout", default_value_t = 30)]
     pub connect_timeout: u64,
@@ -32,19 +236,589 @@ pub struct Cli {
     #[arg(long = "retry-wait", default_value_t = 10)]
     pub retry_wait: u64,
 
+    /// Don't ask servers for gzip-compressed responses
+    #[arg(long = "no-gzip")]
+    pub no_gzip: bool,
+
+    /// Save gzip/deflate Content-Encoding responses decoded, fetching over one
+    /// connection without aria2c
+    #[arg(long, conflicts_with_all = ["no_gzip", "range"])]
+    pub decompress: bool,
+
     /// Custom User-Agent string
     #[arg(long = "user-agent")]
     pub user_agent: Option<String>,
 
+    /// Redirects to follow before giving up (0 fails on any redirect)
+    #[arg(long = "max-redirects", default_value_t = 10)]
+    pub max_redirects: usize,
+
+    /// Connect over IPv4 only
+    #[arg(long, short = '4', conflicts_with = "ipv6")]
+    pub ipv4: bool,
+
+    /// Connect over IPv6 only
+    #[arg(long, short = '6')]
+    pub ipv6: bool,
+
+    /// DNS server to resolve hosts with instead of the system's (repeatable)
+    #[arg(long = "dns-server", value_name = "IP")]
+    pub dns_servers: Vec<std::net::IpAddr>,
+
+    /// When a download can't connect, retry it once over IPv4 only
+    #[arg(long = "ipv-fallback", conflicts_with_all = ["ipv4", "ipv6"])]
+    pub ipv_fallback: bool,
+
+    /// File of HTTP(S) proxy URLs, one per line, assigned to downloads in turn
+    #[arg(long = "proxy-list")]
+    pub proxy_list: Option<String>,
+
+    /// FTP user name (credentials in the URL take precedence)
+    #[arg(long = "ftp-user")]
+    pub ftp_user: Option<String>,
+
+    /// FTP password (credentials in the URL take precedence)
+    #[arg(long = "ftp-password")]
+    pub ftp_password: Option<String>,
+
+    /// Use active instead of passive FTP mode
+    #[arg(long = "ftp-active")]
+    pub ftp_active: bool,
+
+    /// Extra root CA certificate (PEM) to trust
+    #[arg(long = "ca-cert")]
+    pub ca_cert: Option<String>,
+
+    /// Client certificate (PEM) for servers that require mutual TLS
+    #[arg(long = "client-cert", requires = "client_key")]
+    pub client_cert: Option<String>,
+
+    /// Private key (PEM) for --client-cert
+    #[arg(long = "client-key", requires = "client_cert")]
+    pub client_key: Option<String>,
+
+    /// Don't verify TLS certificates (dangerous)
+    #[arg(long)]
+    pub insecure: bool,
+
+    /// Follow redirects from HTTPS to plain HTTP instead of refusing them
+    #[arg(long = "allow-insecure-redirect")]
+    pub allow_insecure_redirect: bool,
+
+    /// URL schemes downloads and their redirects may use, comma-separated
+    /// (e.g. "http,https" to also allow plaintext HTTP)
+    #[arg(
+        long = "allowed-schemes",
+        value_name = "LIST",
+        value_delimiter = ',',
+        default_value = "https"
+    )]
+    pub allowed_schemes: Vec<Scheme>,
+
+    /// Refuse ftp:// URLs, whatever --allowed-schemes says
+    #[arg(long = "no-ftp")]
+    pub no_ftp: bool,
+
+    /// Extra HTTP header for every download (repeatable, e.g. "Referer: https://example.com/")
+    #[arg(long = "header", value_parser = parse_header)]
+    pub headers: Vec<String>,
+
+    /// Read URLs (with optional per-URL options) from a file
+    #[arg(short = 'i', long = "input-file")]
+    pub input_file: Option<String>,
+
+    /// Resume the unfinished downloads recorded in this directory
+    #[arg(
+        long = "resume-all",
+        value_name = "DIR",
+        conflicts_with = "destination"
+    )]
+    pub resume_all: Option<String>,
+
+    /// Run the downloads and settings described in a YAML or JSON job file
+    #[arg(long, value_name = "FILE")]
+    pub job: Option<String>,
+
+    /// Download into this directory and move finished files to the destination
+    #[arg(long = "temp-dir")]
+    pub temp_dir: Option<String>,
+
+    /// Don't probe the destination for write access before starting; rely
+    /// on aria2c's error instead (for append-only or FUSE mounts)
+    #[arg(long = "no-write-check")]
+    pub no_write_check: bool,
+
+    /// Download to <name>.dlrs-part and rename into place only on success
+    #[arg(long)]
+    pub atomic: bool,
+
+    /// Take [1-9] and {a,b} in URLs literally instead of expanding them
+    #[arg(long = "no-glob")]
+    pub no_glob: bool,
+
+    /// Recreate the URL's directory path under the destination
+    #[arg(long = "mirror-structure")]
+    pub mirror_structure: bool,
+
+    /// Extra attempts at the filename lookup after a timeout or 5xx error
+    #[arg(long = "detect-retries", default_value_t = 2)]
+    pub detect_retries: u32,
+
+    /// Ignore `Content-Disposition: inline` names and use the URL's instead
+    #[arg(long = "ignore-inline")]
+    pub ignore_inline: bool,
+
+    /// Where output names come from
+    #[arg(long, value_enum, default_value_t = Naming::Header)]
+    pub naming: Naming,
+
+    /// Name pattern for --naming template: {name}, {stem}, {ext}, {host}, {date}
+    #[arg(long = "name-template", required_if_eq("naming", "template"))]
+    pub name_template: Option<String>,
+
+    /// Directory for each download, relative to --destination unless absolute:
+    /// {type}, {ext}, {host}, {date} (e.g. "{type}/{date}")
+    #[arg(long = "dest-template")]
+    pub dest_template: Option<String>,
+
+    /// Expand directory URLs (ending in /) into the files they list
+    #[arg(long, short = 'r')]
+    pub recursive: bool,
+
+    /// How many levels of subdirectories --recursive descends
+    #[arg(long = "max-depth", default_value_t = 5)]
+    pub max_depth: usize,
+
+    /// Print each URL's resolved name, size and content type, then exit
+    /// without downloading
+    #[arg(long)]
+    pub list: bool,
+
+    /// Treat the downloads as JSON manifests and then fetch the URLs found
+    /// at this dotted path in them (e.g. files.url)
+    #[arg(long = "follow-manifest", value_name = "PATH")]
+    pub follow_manifest: Option<String>,
+
+    /// Rewrite URLs before downloading, as <from-regex>=<to-template> with
+    /// $1 or ${name} for captures (repeatable, applied in order)
+    #[arg(long, value_name = "RULE", value_parser = parse_rewrite)]
+    pub rewrite: Vec<Rewrite>,
+
+    /// Skip URLs matching this glob, or regex with a re: prefix (repeatable)
+    #[arg(long, value_name = "PATTERN", value_parser = parse_exclude)]
+    pub exclude: Vec<String>,
+
+    /// File of --exclude patterns, one per line
+    #[arg(long = "exclude-file")]
+    pub exclude_file: Option<String>,
+
+    /// Check that the network is reachable with one HEAD request before
+    /// starting, and stop early if it isn't
+    #[arg(long)]
+    pub preflight: bool,
+
+    /// URL for --preflight to check (default: the first HTTP(S) download)
+    #[arg(long = "preflight-url", requires = "preflight", value_parser = validate_url)]
+    pub preflight_url: Option<String>,
+
+    /// Resolve all filenames up front, before any download starts
+    #[arg(long = "prefetch-names")]
+    pub prefetch_names: bool,
+
+    /// Skip files that already exist in the destination (same as
+    /// --overwrite-policy skip)
+    #[arg(long = "skip-existing")]
+    pub skip_existing: bool,
+
+    /// What to do when a finished file with the same name already exists
+    /// [default: ask on a terminal, else overwrite]
+    #[arg(
+        long = "overwrite-policy",
+        value_enum,
+        conflicts_with = "skip_existing"
+    )]
+    pub overwrite_policy: Option<OverwritePolicy>,
+
+    /// Never prompt; apply --overwrite-policy to existing files
+    #[arg(long = "non-interactive")]
+    pub non_interactive: bool,
+
+    /// Skip files whose remote Last-Modified isn't newer than the local copy
+    #[arg(long = "if-newer")]
+    pub if_newer: bool,
+
+    /// Skip files whose ETag matches the one recorded on the last run
+    #[arg(long)]
+    pub cache: bool,
+
+    /// Don't resume partially downloaded files without a control file
+    #[arg(long = "no-continue")]
+    pub no_continue: bool,
+
+    /// Delete the partial file and .aria2 control file when a download fails
+    /// (interrupted downloads are kept for resuming)
+    #[arg(long = "clean-on-failure")]
+    pub clean_on_failure: bool,
+
+    /// Delete any existing file and .aria2 control file before downloading
+    #[arg(long, conflicts_with = "skip_existing")]
+    pub truncate: bool,
+
+    /// Download only this inclusive byte range of an HTTP URL (e.g., 0-1M)
+    #[arg(long, value_parser = parse_byte_range)]
+    pub range: Option<ByteRange>,
+
+    /// Download HTTP(S) files as separate byte-range chunks of this size,
+    /// each kept and retried on its own, then joined (e.g. 64M)
+    #[arg(long, value_name = "SIZE", value_parser = parse_chunk_size, conflicts_with_all = ["range", "decompress"])]
+    pub chunked: Option<u64>,
+
+    /// Extra aria2c option as key=value, e.g. -X dscp=8 (repeatable; wins over dlrs's own)
+    #[arg(
+        short = 'X',
+        value_name = "KEY=VALUE",
+        value_parser = parse_aria2_option,
+        allow_hyphen_values = true
+    )]
+    pub aria2_options: Vec<String>,
+
+    /// Pass aria2c only where the file goes, the overwrite policy, plain
+    /// progress output, the URL's speed limit, proxy, checksum and headers,
+    /// the -X options and the URL, dropping dlrs's other defaults and
+    /// mapped options
+    #[arg(long = "no-defaults")]
+    pub no_defaults: bool,
+
+    /// aria2c config file to load (by default aria2c's own is ignored)
+    #[arg(long = "aria2-conf")]
+    pub aria2_conf: Option<String>,
+
+    /// Fail and delete downloads smaller than this size (e.g., 1K)
+    #[arg(long = "min-size", value_parser = parse_size)]
+    pub min_size: Option<u64>,
+
+    /// Fail binary downloads (.zip, .iso, ...) that turn out to be HTML pages
+    #[arg(long = "detect-soft-errors")]
+    pub detect_soft_errors: bool,
+
     /// Number of parallel downloads (batch mode)
     #[arg(long = "parallel", default_value_t = 2)]
     pub parallel_downloads: usize,
 
+    /// Start at most this many downloads per second (e.g., 2 or 0.5)
+    #[arg(long = "ramp-up", value_name = "PER_SEC", value_parser = parse_start_rate)]
+    pub ramp_up: Option<f64>,
+
+    /// Tune the number of parallel downloads to the measured throughput
+    #[arg(long = "auto-parallel", conflicts_with = "max_overall_speed")]
+    pub auto_parallel: bool,
+
+    /// Serve Prometheus metrics on this address (e.g., 127.0.0.1:9100)
+    #[cfg(feature = "metrics")]
+    #[arg(long = "metrics-addr")]
+    pub metrics_addr: Option<String>,
+
+    /// Stop seeding once this share ratio is reached (torrents only)
+    #[arg(long = "seed-ratio")]
+    pub seed_ratio: Option<f64>,
+
+    /// Disable DHT peer discovery (torrents only)
+    #[arg(long = "no-dht")]
+    pub no_dht: bool,
+
+    /// Port to listen on for BitTorrent peers (torrents only)
+    #[arg(long = "bt-port")]
+    pub bt_port: Option<u16>,
+
+    /// Download pieces in order so the file is usable before it completes
+    /// (torrents fetch the first and last pieces first)
+    #[arg(long)]
+    pub sequential: bool,
+
+    /// Append aria2c's debug log for every download to this file
+    #[arg(long = "log-file")]
+    pub log_file: Option<String>,
+
+    /// Progress display: bars on a terminal and plain lines otherwise (auto),
+    /// always bars, or always plain lines
+    #[arg(long, value_enum, default_value_t = ProgressMode::Auto)]
+    pub progress: ProgressMode,
+
+    /// Progress for people (see --progress), or stable key=value lines
+    /// for frontends
+    #[arg(long = "progress-format", value_enum, default_value_t = ProgressFormat::Human)]
+    pub progress_format: ProgressFormat,
+
+    /// File descriptor for --progress-format machine lines
+    #[arg(long = "progress-fd", default_value_t = 2)]
+    pub progress_fd: i32,
+
+    /// Send progress events as JSON lines to the Unix socket (on Windows,
+    /// the named pipe) at <PATH> instead of drawing them
+    #[arg(long = "progress-socket", value_name = "PATH")]
+    pub progress_socket: Option<String>,
+
+    /// Press Enter during the downloads to list the running ones and cancel
+    /// one of them, leaving the rest going (needs a terminal)
+    #[arg(long = "cancel-menu")]
+    pub cancel_menu: bool,
+
     /// Suppress progress display
     #[arg(long, short = 'q')]
     pub quiet: bool,
 
+    /// Shell command to run after each file finishes; DLRS_FILE, DLRS_URL
+    /// and DLRS_DIR describe it, plus DLRS_CHECKSUM_OK and DLRS_CHECKSUM
+    /// when it has a checksum
+    #[arg(long = "on-complete", value_name = "CMD")]
+    pub on_complete: Option<String>,
+
+    /// Shell command to run once the batch finishes, even if some downloads
+    /// failed; DLRS_TOTAL, DLRS_SUCCEEDED, DLRS_FAILED and DLRS_DIR
+    /// describe the run
+    #[arg(long = "after-batch", value_name = "CMD")]
+    pub after_batch: Option<String>,
+
+    /// When another dlrs is downloading the same file, wait for it to finish
+    /// instead of failing
+    #[arg(long = "wait-lock")]
+    pub wait_lock: bool,
+
+    /// Exit with an error when a hook command fails, instead of warning
+    #[arg(long = "strict-hooks")]
+    pub strict_hooks: bool,
+
+    /// Keep a running monthly total of downloaded bytes and print it
+    #[arg(long = "track-usage")]
+    pub track_usage: bool,
+
+    /// Hide [INFO], [SUCCESS] and [WARNING] lines but keep the progress display
+    #[arg(long = "no-logs")]
+    pub no_logs: bool,
+
+    /// Output filename for the URL that follows it (repeatable)
+    #[arg(short = 'o', long = "output")]
+    pub outputs: Vec<String>,
+
     /// URLs to download
-    #[arg(required = true)]
     pub urls: Vec<String>,
 }
+
+/// Checks that every `-o` directly precedes its own URL, so `outputs[i]`
+/// names `urls[i]`: `-o a.zip url1 -o b.zip url2`.
+pub fn check_output_pairs(matches: &ArgMatches) -> Result<(), String> {
+    let Some(outputs) = matches.indices_of("outputs") else {
+        return Ok(());
+    };
+    let outputs = outputs.collect::<Vec<_>>();
+    let urls = matches
+        .indices_of("urls")
+        .map(|i| i.collect::<Vec<_>>())
+        .unwrap_or_default();
+
+    if outputs.len() != urls.len() {
+        return Err(format!(
+            "got {} -o name(s) for {} URL(s); give one -o before each URL or none at all",
+            outputs.len(),
+            urls.len()
+        ));
+    }
+    for (i, (o, u)) in outputs.iter().zip(&urls).enumerate() {
+        if o > u || outputs.get(i + 1).is_some_and(|next| next < u) {
+            return Err(format!(
+                "-o must come directly before the URL it names (URL {})",
+                i + 1
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Inserts `get` when no subcommand is given, so `dlrs <url>` keeps working.
+pub fn with_default_subcommand(mut args: Vec<String>) -> Vec<String> {
+    let explicit = args.get(1).is_some_and(|a| {
+        SUBCOMMANDS.contains(&a.as_str())
+            || TOP_LEVEL_FLAGS.contains(&a.as_str())
+            || a.starts_with("--completions=")
+    });
+    if !explicit {
+        let at = args.len().min(1);
+        args.insert(at, "get".to_string());
+    }
+    args
+}
+
+/// Whether the arguments run a subcommand that takes the download options.
+pub fn is_download_command(args: &[String]) -> bool {
+    matches!(args.get(1).map(String::as_str), Some("get" | "resume"))
+}
+
+impl GetArgs {
+    /// Whether progress goes to a frontend (--progress-format machine or
+    /// --progress-socket) rather than a person.
+    pub fn machine_progress(&self) -> bool {
+        self.progress_format == ProgressFormat::Machine || self.progress_socket.is_some()
+    }
+
+    /// Whether progress should be reported as plain text lines instead of bars.
+    pub fn plain_progress(&self) -> bool {
+        // Without the tui feature there are no bars to draw
+        if !cfg!(feature = "tui") {
+            return true;
+        }
+        match self.progress {
+            ProgressMode::Auto => !std::io::stdout().is_terminal(),
+            ProgressMode::Always => false,
+            ProgressMode::Plain => true,
+        }
+    }
+
+    /// `--overwrite-policy`, or `skip` for `--skip-existing`.
+    pub fn effective_overwrite_policy(&self) -> OverwritePolicy {
+        if self.skip_existing {
+            OverwritePolicy::Skip
+        } else {
+            self.overwrite_policy.unwrap_or(OverwritePolicy::Overwrite)
+        }
+    }
+
+    /// Whether to ask before replacing an existing file: only when no
+    /// policy was chosen and someone is at the terminal to answer.
+    pub fn prompts_on_overwrite(&self) -> bool {
+        self.overwrite_policy.is_none()
+            && !self.skip_existing
+            && !self.truncate
+            && !self.quiet
+            && !self.non_interactive
+            && !self.cancel_menu
+            && !self.machine_progress()
+            && std::io::stdin().is_terminal()
+            && std::io::stderr().is_terminal()
+    }
+
+    pub fn has_torrent_options(&self) -> bool {
+        self.seed_ratio.is_some() || self.no_dht || self.bt_port.is_some()
+    }
+
+    /// `--allowed-schemes`, less `ftp` under `--no-ftp`.
+    pub fn allowed_schemes(&self) -> Vec<Scheme> {
+        let mut allowed = self.allowed_schemes.clone();
+        if self.no_ftp {
+            allowed.retain(|s| *s != Scheme::Ftp);
+        }
+        allowed
+    }
+
+    /// Fails for a (validated) URL whose scheme `--allowed-schemes` or
+    /// `--no-ftp` rules out.
+    pub fn check_scheme(&self, url: &str) -> Result<(), DlrsError> {
+        let allowed = self.allowed_schemes();
+        let scheme = url.split(':').next().unwrap_or("");
+        if allowed.iter().any(|s| s.as_str() == scheme) {
+            return Ok(());
+        }
+        let names: Vec<&str> = allowed.iter().map(|s| s.as_str()).collect();
+        Err(DlrsError::InvalidUrl {
+            url: redact_url(url),
+            reason: format!(
+                "scheme '{}' is not allowed (allowed: {}; see --allowed-schemes)",
+                scheme,
+                if names.is_empty() {
+                    "none".to_string()
+                } else {
+                    names.join(", ")
+                }
+            ),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::{CommandFactory, FromArgMatches};
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_with_default_subcommand() {
+        assert_eq!(
+            with_default_subcommand(args(&["dlrs", "-d", "/tmp", "https://example.com/a"])),
+            args(&["dlrs", "get", "-d", "/tmp", "https://example.com/a"])
+        );
+        assert_eq!(
+            with_default_subcommand(args(&["dlrs"])),
+            args(&["dlrs", "get"])
+        );
+        assert_eq!(
+            with_default_subcommand(args(&["dlrs", "resume", "s.txt"])),
+            args(&["dlrs", "resume", "s.txt"])
+        );
+        assert_eq!(
+            with_default_subcommand(args(&["dlrs", "--completions", "zsh"])),
+            args(&["dlrs", "--completions", "zsh"])
+        );
+    }
+
+    #[test]
+    fn test_resume_takes_download_options() {
+        let cli = Cli::parse_from(["dlrs", "resume", "s.txt", "--parallel", "4"]);
+        let Some(Command::Resume(resume)) = cli.command else {
+            panic!("expected resume");
+        };
+        assert_eq!(resume.session, "s.txt");
+        assert_eq!(resume.options.parallel_downloads, 4);
+
+        assert!(Cli::try_parse_from(["dlrs", "resume", "s.txt", "-i", "urls.txt"]).is_err());
+    }
+
+    #[test]
+    fn test_check_output_pairs() {
+        let check = |argv: &[&str]| {
+            let matches = GetArgs::command().get_matches_from(argv);
+            let cli = GetArgs::from_arg_matches(&matches).unwrap();
+            check_output_pairs(&matches).map(|_| cli)
+        };
+
+        let cli = check(&["dlrs", "-o", "a.zip", "u1", "-o", "b.zip", "u2"]).unwrap();
+        assert_eq!(cli.outputs, vec!["a.zip", "b.zip"]);
+        assert_eq!(cli.urls, vec!["u1", "u2"]);
+        assert!(check(&["dlrs", "u1", "u2"]).is_ok());
+
+        assert!(check(&["dlrs", "-o", "a.zip", "u1", "u2"]).is_err());
+        assert!(check(&["dlrs", "-o", "a.zip", "-o", "b.zip", "u1", "u2"]).is_err());
+        assert!(check(&["dlrs", "u1", "-o", "a.zip", "u2", "-o", "b.zip"]).is_err());
+    }
+
+    #[test]
+    fn test_check_scheme() {
+        let get = |argv: &[&str]| GetArgs::try_parse_from(argv).unwrap();
+
+        // Only TLS unless told otherwise
+        let default = get(&["dlrs", "x"]);
+        assert!(default.check_scheme("https://example.com/a").is_ok());
+        let err = default.check_scheme("http://example.com/a").unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("scheme 'http' is not allowed (allowed: https")
+        );
+        assert!(default.check_scheme("magnet:?xt=urn:btih:abc").is_err());
+
+        let all = ["dlrs", "--allowed-schemes", "http,https,ftp,magnet"];
+        let any = get(&[&all[..], &["x"]].concat());
+        assert!(any.check_scheme("http://example.com/a").is_ok());
+        assert!(any.check_scheme("magnet:?xt=urn:btih:abc").is_ok());
+
+        let no_ftp = get(&[&all[..], &["--no-ftp", "x"]].concat());
+        assert!(no_ftp.check_scheme("ftp://example.com/a").is_err());
+        let err = default
+            .check_scheme("ftp://user:pw@example.com/a")
+            .unwrap_err();
+        assert!(!err.to_string().contains("pw"));
+        assert!(no_ftp.check_scheme("http://example.com/a").is_ok());
+
+        assert!(GetArgs::try_parse_from(["dlrs", "--allowed-schemes", "https,ssh", "x"]).is_err());
+    }
+}
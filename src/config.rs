@@ -0,0 +1,113 @@
+use crate::utils::expand_path;
+use anyhow::{Context, Result};
+use std::path::PathBuf;
+
+/// Path options whose values from a file may use `~` and `$VARS`. On the
+/// command line the shell has already expanded them, and expanding again
+/// would mangle a literal `~` or `$` it left alone.
+const EXPANDED_OPTIONS: &[&str] = &["destination", "resume-all", "temp-dir", "progress-socket"];
+
+/// `--key=value`, with `~` and `$VARS` expanded in the path options.
+pub fn file_option(key: &str, value: &str) -> Result<String> {
+    if EXPANDED_OPTIONS.contains(&key) {
+        Ok(format!("--{}={}", key, expand_path(value)?))
+    } else {
+        Ok(format!("--{}={}", key, value))
+    }
+}
+
+/// Location of the config file: `$DLRS_CONFIG`, else `$XDG_CONFIG_HOME/dlrs/config`,
+/// else `~/.config/dlrs/config`.
+fn config_path() -> Option<(PathBuf, bool)> {
+    if let Some(p) = std::env::var_os("DLRS_CONFIG") {
+        return Some((PathBuf::from(p), true));
+    }
+    let base = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|h| PathBuf::from(h).join(".config")))?;
+    Some((base.join("dlrs").join("config"), false))
+}
+
+/// Turns `key = value` lines into `--key=value` arguments.
+///
+/// `true` values become bare flags and `false` values are dropped, so any
+/// long option can be set from the config file.
+pub fn parse_config(contents: &str) -> Result<Vec<String>> {
+    let mut args = Vec::new();
+
+    for (idx, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let (key, value) = line
+            .split_once('=')
+            .context(format!("Config line {}: expected 'key = value'", idx + 1))?;
+        let key = key.trim().trim_start_matches("--");
+        let value = value.trim().trim_matches('"');
+        if key.is_empty() {
+            anyhow::bail!("Config line {}: missing key", idx + 1);
+        }
+
+        match value {
+            "true" => args.push(format!("--{}", key)),
+            "false" => {}
+            _ => args.push(file_option(key, value).context(format!("Config line {}", idx + 1))?),
+        }
+    }
+
+    Ok(args)
+}
+
+/// Inserts config file options at `insert_at` (right after the subcommand),
+/// ahead of the command line ones, so explicit flags override the config file.
+pub fn args_with_config(mut args: Vec<String>, insert_at: usize) -> Result<Vec<String>> {
+    let Some((path, explicit)) = config_path() else {
+        return Ok(args);
+    };
+
+    let contents = match std::fs::read_to_string(&path) {
+        Ok(c) => c,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound && !explicit => return Ok(args),
+        Err(e) => {
+            return Err(e).context(format!("Failed to read config file {:?}", path));
+        }
+    };
+
+    let config_args = parse_config(&contents).context(format!("Invalid config file {:?}", path))?;
+    let insert_at = args.len().min(insert_at);
+    args.splice(insert_at..insert_at, config_args);
+    Ok(args)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_config() {
+        let contents = "# comment\n\nmax-speed = 2M\nquiet = true\nparallel=4\nfoo = false\nspeed-schedule = 09:00-18:00=1M, 18:00-09:00=0\n";
+        assert_eq!(
+            parse_config(contents).unwrap(),
+            vec![
+                "--max-speed=2M",
+                "--quiet",
+                "--parallel=4",
+                "--speed-schedule=09:00-18:00=1M, 18:00-09:00=0",
+            ]
+        );
+
+        let home = std::env::var("HOME").unwrap();
+        assert_eq!(
+            parse_config("destination = ~/dl\ntemp-dir = $HOME/tmp\nreferer = ~x").unwrap(),
+            vec![
+                format!("--destination={}/dl", home),
+                format!("--temp-dir={}/tmp", home),
+                "--referer=~x".to_string()
+            ]
+        );
+        assert!(parse_config("max-speed").is_err());
+        assert!(parse_config(" = 1").is_err());
+    }
+}
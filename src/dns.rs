@@ -0,0 +1,41 @@
+use hickory_resolver::TokioResolver;
+use hickory_resolver::config::{NameServerConfig, ResolverConfig};
+use hickory_resolver::net::runtime::TokioRuntimeProvider;
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+
+/// Resolves names through the `--dns-server` addresses instead of the
+/// system resolver, for the probes and single-connection downloads. aria2c
+/// gets the same servers through `--async-dns-server`.
+pub struct DnsServers {
+    resolver: Arc<TokioResolver>,
+}
+
+impl DnsServers {
+    pub fn new(servers: &[IpAddr]) -> anyhow::Result<Self> {
+        let config = ResolverConfig::from_name_servers(
+            servers
+                .iter()
+                .map(|ip| NameServerConfig::udp_and_tcp(*ip))
+                .collect(),
+        );
+        let resolver =
+            TokioResolver::builder_with_config(config, TokioRuntimeProvider::default()).build()?;
+        Ok(DnsServers {
+            resolver: Arc::new(resolver),
+        })
+    }
+}
+
+impl Resolve for DnsServers {
+    fn resolve(&self, name: Name) -> Resolving {
+        let resolver = self.resolver.clone();
+        Box::pin(async move {
+            let lookup = resolver.lookup_ip(name.as_str()).await?;
+            // The port is filled in by the connector
+            let addrs: Vec<SocketAddr> = lookup.iter().map(|ip| SocketAddr::new(ip, 0)).collect();
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
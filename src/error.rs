@@ -0,0 +1,190 @@
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum DlrsError {
+    #[error("cancelled")]
+    Cancelled,
+
+    #[error("invalid URL '{url}': {reason}")]
+    InvalidUrl { url: String, reason: String },
+
+    #[error("file not found or access denied")]
+    NotFound,
+
+    #[error("not enough disk space available")]
+    DiskFull,
+
+    #[error("network timeout or connection failure")]
+    Network,
+
+    #[error("download speed stayed below --min-speed")]
+    TooSlow,
+
+    #[error("not finished within --per-file-deadline of {secs}s")]
+    DeadlineExceeded { secs: u64 },
+
+    #[error("checksum mismatch")]
+    ChecksumMismatch,
+
+    #[error("file is {size} bytes, below --min-size of {min} bytes")]
+    TooSmall { size: u64, min: u64 },
+
+    #[error("{failed} file(s) failed verification, {missing} missing")]
+    VerifyFailed { failed: usize, missing: usize },
+
+    #[error("refusing redirect from HTTPS to insecure '{to}' (use --allow-insecure-redirect)")]
+    InsecureRedirect { to: String },
+
+    #[error("more than {limit} redirects (see --max-redirects)")]
+    TooManyRedirects { limit: usize },
+
+    #[error("refusing redirect to '{to}': its scheme isn't in --allowed-schemes")]
+    SchemeRedirect { to: String },
+
+    #[error("{reason}")]
+    SoftError { reason: String },
+
+    #[error("response is larger than the {limit}-byte limit")]
+    TooLarge { limit: u64 },
+
+    #[error("server ignored the byte range (HTTP {status}); it may not support --range")]
+    RangeIgnored { status: u16 },
+
+    #[error("another dlrs is already downloading {file} (use --wait-lock to wait for it)")]
+    Locked { file: String },
+
+    #[error("aria2c failed with exit code {code}")]
+    Aria2Failed { code: i32 },
+
+    #[error("aria2c terminated by signal")]
+    Aria2Killed,
+
+    #[error("{} of {total} downloads failed:\n{}", .errors.len(), list_errors(.errors))]
+    BatchFailed {
+        total: usize,
+        errors: Vec<anyhow::Error>,
+    },
+}
+
+impl DlrsError {
+    /// Maps an aria2c exit status to an error.
+    pub fn from_aria2_exit(code: Option<i32>) -> Self {
+        match code {
+            Some(3) | Some(4) => DlrsError::NotFound,
+            Some(5) => DlrsError::TooSlow,
+            Some(9) => DlrsError::DiskFull,
+            Some(2) | Some(6) | Some(19) => DlrsError::Network,
+            Some(32) => DlrsError::ChecksumMismatch,
+            Some(code) => DlrsError::Aria2Failed { code },
+            None => DlrsError::Aria2Killed,
+        }
+    }
+}
+
+/// Process exit code for an error.
+///
+/// A batch exits with its failures' shared code, or 1 if they differ.
+pub fn exit_code(err: &anyhow::Error) -> i32 {
+    match err.downcast_ref::<DlrsError>() {
+        Some(DlrsError::Cancelled) => 130,
+        Some(DlrsError::InvalidUrl { .. }) => 2,
+        Some(DlrsError::NotFound) => 3,
+        Some(DlrsError::DiskFull) => 9,
+        Some(DlrsError::Network)
+        | Some(DlrsError::TooSlow)
+        | Some(DlrsError::DeadlineExceeded { .. }) => 28,
+        Some(DlrsError::ChecksumMismatch) | Some(DlrsError::VerifyFailed { .. }) => 32,
+        Some(DlrsError::BatchFailed { errors, .. }) => {
+            let mut codes = errors.iter().map(exit_code);
+            let first = codes.next().unwrap_or(1);
+            if codes.all(|c| c == first) { first } else { 1 }
+        }
+        Some(DlrsError::TooSmall { .. })
+        | Some(DlrsError::InsecureRedirect { .. })
+        | Some(DlrsError::TooManyRedirects { .. })
+        | Some(DlrsError::SchemeRedirect { .. })
+        | Some(DlrsError::SoftError { .. })
+        | Some(DlrsError::RangeIgnored { .. })
+        | Some(DlrsError::TooLarge { .. })
+        | Some(DlrsError::Locked { .. })
+        | Some(DlrsError::Aria2Failed { .. })
+        | Some(DlrsError::Aria2Killed)
+        | None => 1,
+    }
+}
+
+pub fn is_cancelled(err: &anyhow::Error) -> bool {
+    matches!(err.downcast_ref::<DlrsError>(), Some(DlrsError::Cancelled))
+}
+
+fn list_errors(errors: &[anyhow::Error]) -> String {
+    errors
+        .iter()
+        .map(|e| format!("  {:#}", e))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_aria2_exit() {
+        assert!(matches!(
+            DlrsError::from_aria2_exit(Some(3)),
+            DlrsError::NotFound
+        ));
+        assert!(matches!(
+            DlrsError::from_aria2_exit(Some(9)),
+            DlrsError::DiskFull
+        ));
+        assert!(matches!(
+            DlrsError::from_aria2_exit(Some(6)),
+            DlrsError::Network
+        ));
+        assert!(matches!(
+            DlrsError::from_aria2_exit(Some(32)),
+            DlrsError::ChecksumMismatch
+        ));
+        assert!(matches!(
+            DlrsError::from_aria2_exit(Some(28)),
+            DlrsError::Aria2Failed { code: 28 }
+        ));
+        assert!(matches!(
+            DlrsError::from_aria2_exit(None),
+            DlrsError::Aria2Killed
+        ));
+    }
+
+    #[test]
+    fn test_exit_code() {
+        let err = |e: DlrsError| anyhow::Error::from(e).context("Failed: https://a/b");
+        assert_eq!(exit_code(&err(DlrsError::Cancelled)), 130);
+        assert_eq!(exit_code(&err(DlrsError::NotFound)), 3);
+        assert_eq!(
+            exit_code(&err(DlrsError::DeadlineExceeded { secs: 60 })),
+            28
+        );
+        assert_eq!(exit_code(&anyhow::anyhow!("other")), 1);
+
+        let same = DlrsError::BatchFailed {
+            total: 3,
+            errors: vec![err(DlrsError::DiskFull), err(DlrsError::DiskFull)],
+        };
+        assert_eq!(exit_code(&same.into()), 9);
+
+        let mixed = DlrsError::BatchFailed {
+            total: 3,
+            errors: vec![err(DlrsError::DiskFull), err(DlrsError::Network)],
+        };
+        assert_eq!(exit_code(&mixed.into()), 1);
+    }
+
+    #[test]
+    fn test_is_cancelled_through_context() {
+        let err = anyhow::Error::from(DlrsError::Cancelled).context("Failed: https://a/b");
+        assert!(is_cancelled(&err));
+        assert!(!is_cancelled(&anyhow::anyhow!("cancelled")));
+    }
+}
@@ -0,0 +1,180 @@
+use crate::engine::DownloadItem;
+use crate::utils::{normalize_size, parse_exclude, parse_header, redact_url, validate_url};
+use anyhow::{Context, Result};
+
+/// Parses an aria2c-style input file.
+///
+/// Each URL sits on its own line; indented `key=value` lines that follow it
+/// (`out`, `header`, `checksum`) apply to that URL only. Blank lines and
+/// lines starting with `#` are ignored.
+pub fn parse_input_file(contents: &str) -> Result<Vec<DownloadItem>> {
+    let mut items: Vec<DownloadItem> = Vec::new();
+
+    for (idx, line) in contents.lines().enumerate() {
+        let lineno = idx + 1;
+        if line.trim().is_empty() || line.trim_start().starts_with('#') {
+            continue;
+        }
+
+        if !line.starts_with([' ', '\t']) {
+            items.push(DownloadItem::new(line.trim().to_string()));
+            continue;
+        }
+
+        let item = items
+            .last_mut()
+            .context(format!("Line {}: option given before any URL", lineno))?;
+        let (key, value) = line
+            .trim()
+            .split_once('=')
+            .context(format!("Line {}: expected 'key=value'", lineno))?;
+        let value = value.trim().to_string();
+
+        match key.trim() {
+            "out" => item.options.out = Some(value),
+            "dir" => item.options.dir = Some(value),
+            "header" => item
+                .options
+                .headers
+                .push(parse_header(&value).context(format!("Line {}", lineno))?),
+            "checksum" => {
+                if !value.contains('=') {
+                    anyhow::bail!("Line {}: checksum must be '<type>=<digest>'", lineno);
+                }
+                item.options.checksum = Some(value);
+            }
+            "max-speed" => {
+                let value = normalize_size(&value)
+                    .context(format!("Line {}: invalid max-speed", lineno))?;
+                item.options.max_speed = Some(value);
+            }
+            "mirror" => item
+                .options
+                .mirrors
+                .push(validate_url(&value).context(format!("Line {}: invalid mirror", lineno))?),
+            k => anyhow::bail!(
+                "Line {}: unsupported option '{}' (supported: out, dir, header, checksum, max-speed, mirror)",
+                lineno,
+                k
+            ),
+        }
+    }
+
+    Ok(items)
+}
+
+/// Parses a `--proxy-list` file: one `http://` or `https://` proxy URL per
+/// line. Blank lines and `#` comments are ignored; anything else that isn't
+/// a usable proxy is returned in the second list so it can be reported and
+/// skipped.
+pub fn parse_proxy_list(contents: &str) -> (Vec<String>, Vec<String>) {
+    let mut proxies = Vec::new();
+    let mut skipped = Vec::new();
+
+    for line in contents.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        match url::Url::parse(line) {
+            Ok(u) if matches!(u.scheme(), "http" | "https") && u.host_str().is_some() => {
+                proxies.push(line.to_string())
+            }
+            _ => skipped.push(redact_url(line)),
+        }
+    }
+
+    (proxies, skipped)
+}
+
+pub fn read_proxy_list(path: &str) -> Result<(Vec<String>, Vec<String>)> {
+    let contents =
+        std::fs::read_to_string(path).context(format!("Failed to read proxy list '{}'", path))?;
+    Ok(parse_proxy_list(&contents))
+}
+
+/// Reads `--exclude-file`: one pattern per line, with blank lines and `#`
+/// comments ignored.
+pub fn read_exclude_file(path: &str) -> Result<Vec<String>> {
+    let contents =
+        std::fs::read_to_string(path).context(format!("Failed to read exclude file '{}'", path))?;
+    let mut patterns = Vec::new();
+    for (idx, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        patterns.push(parse_exclude(line).context(format!("{}: line {}", path, idx + 1))?);
+    }
+    Ok(patterns)
+}
+
+pub fn read_input_file(path: &str) -> Result<Vec<DownloadItem>> {
+    let contents =
+        std::fs::read_to_string(path).context(format!("Failed to read input file '{}'", path))?;
+    parse_input_file(&contents).context(format!("Invalid input file '{}'", path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_input_file() {
+        let contents = "\
+# nightly builds
+https://example.com/a.zip
+  out=renamed.zip
+  header=Referer: https://example.com/
+\theader=X-Token: abc
+
+https://example.com/b.iso
+  checksum=sha-256=0123abcd
+  max-speed=500K
+  mirror=https://mirror1.example.com/b.iso
+  mirror=https://mirror2.example.com/b.iso
+";
+        let items = parse_input_file(contents).unwrap();
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].url, "https://example.com/a.zip");
+        assert_eq!(items[0].options.out.as_deref(), Some("renamed.zip"));
+        assert_eq!(
+            items[0].options.headers,
+            vec!["Referer: https://example.com/", "X-Token: abc"]
+        );
+        assert_eq!(
+            items[1].options.checksum.as_deref(),
+            Some("sha-256=0123abcd")
+        );
+        assert!(items[1].options.out.is_none());
+        assert_eq!(items[1].options.max_speed.as_deref(), Some("500K"));
+        assert_eq!(items[1].options.mirrors.len(), 2);
+        assert!(items[0].options.mirrors.is_empty());
+
+        assert!(parse_input_file("  out=a.zip\nhttps://example.com/a.zip").is_err());
+        assert!(parse_input_file("https://example.com/a\n  referer=x").is_err());
+        assert!(parse_input_file("https://example.com/a\n  mirror=gopher://m/a").is_err());
+        assert!(parse_input_file("https://example.com/a\n  checksum=abc").is_err());
+        assert!(parse_input_file("https://example.com/a\n  max-speed=fast").is_err());
+    }
+
+    #[test]
+    fn test_parse_proxy_list() {
+        let contents = "\
+# pool
+http://10.0.0.1:3128
+
+https://user:pw@proxy.example.com:8443
+socks5://10.0.0.2:1080
+not a proxy
+";
+        let (proxies, skipped) = parse_proxy_list(contents);
+        assert_eq!(
+            proxies,
+            vec![
+                "http://10.0.0.1:3128",
+                "https://user:pw@proxy.example.com:8443"
+            ]
+        );
+        assert_eq!(skipped.len(), 2);
+    }
+}
@@ -0,0 +1,241 @@
+use crate::config::file_option;
+use crate::engine::DownloadItem;
+use crate::utils::{normalize_size, parse_header, validate_url};
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// A `--job` file: global settings (any long option, as in the config file)
+/// and the downloads themselves.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct JobSpec {
+    #[serde(default)]
+    pub settings: BTreeMap<String, Value>,
+    pub downloads: Vec<JobDownload>,
+}
+
+/// One entry of `downloads`: a URL (or several sharing these options) and
+/// the same per-URL options the input file takes.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct JobDownload {
+    pub url: Option<String>,
+    #[serde(default)]
+    pub urls: Vec<String>,
+    pub out: Option<String>,
+    pub checksum: Option<String>,
+    #[serde(default)]
+    pub headers: Vec<String>,
+    pub max_speed: Option<String>,
+    pub proxy: Option<String>,
+    #[serde(default)]
+    pub mirrors: Vec<String>,
+}
+
+/// Reads a job file; `.json` files are parsed as JSON, anything else as
+/// YAML. Parse and schema errors carry the line and column.
+pub fn load_job(path: &str) -> Result<JobSpec> {
+    let contents =
+        std::fs::read_to_string(path).context(format!("Failed to read job file '{}'", path))?;
+    let is_json = Path::new(path)
+        .extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("json"));
+    let spec = if is_json {
+        serde_json::from_str(&contents).map_err(anyhow::Error::from)
+    } else {
+        serde_yaml_ng::from_str(&contents).map_err(anyhow::Error::from)
+    };
+    spec.context(format!("Invalid job file '{}'", path))
+}
+
+impl JobSpec {
+    /// `settings` as command-line arguments: `true` becomes a bare flag,
+    /// `false` is dropped and a list repeats the option.
+    pub fn settings_args(&self) -> Result<Vec<String>> {
+        let mut args = Vec::new();
+        for (key, value) in &self.settings {
+            let key = key.trim_start_matches("--");
+            let values = match value {
+                Value::Array(items) => items.iter().collect(),
+                _ => vec![value],
+            };
+            for value in values {
+                match value {
+                    Value::Bool(true) => args.push(format!("--{}", key)),
+                    Value::Bool(false) | Value::Null => {}
+                    Value::String(s) => {
+                        args.push(file_option(key, s).context(format!("settings.{}", key))?)
+                    }
+                    Value::Number(n) => args.push(format!("--{}={}", key, n)),
+                    _ => anyhow::bail!("settings.{}: expected a string, number or boolean", key),
+                }
+            }
+        }
+        Ok(args)
+    }
+
+    /// The downloads, checked the same way as input file entries.
+    pub fn downloads(&self) -> Result<Vec<DownloadItem>> {
+        let mut items = Vec::new();
+        for (i, entry) in self.downloads.iter().enumerate() {
+            let at = || format!("downloads[{}]", i);
+            let urls: Vec<&String> = entry.url.iter().chain(&entry.urls).collect();
+            if urls.is_empty() {
+                anyhow::bail!("{}: needs a url or urls", at());
+            }
+            if urls.len() > 1 && entry.out.is_some() {
+                anyhow::bail!(
+                    "{}: out names a single file but there are several urls",
+                    at()
+                );
+            }
+            if let Some(checksum) = &entry.checksum
+                && !checksum.contains('=')
+            {
+                anyhow::bail!("{}: checksum must be '<type>=<digest>'", at());
+            }
+            let headers = entry
+                .headers
+                .iter()
+                .map(|h| parse_header(h))
+                .collect::<Result<Vec<_>>>()
+                .context(at())?;
+            let max_speed = entry
+                .max_speed
+                .as_deref()
+                .map(normalize_size)
+                .transpose()
+                .context(format!("{}: invalid max-speed", at()))?;
+            let mirrors = entry
+                .mirrors
+                .iter()
+                .map(|m| validate_url(m))
+                .collect::<Result<Vec<_>>>()
+                .context(format!("{}: invalid mirror", at()))?;
+
+            for url in urls {
+                let mut item = DownloadItem::new(url.trim().to_string());
+                item.options.out = entry.out.clone();
+                item.options.checksum = entry.checksum.clone();
+                item.options.headers = headers.clone();
+                item.options.max_speed = max_speed.clone();
+                item.options.proxy = entry.proxy.clone();
+                item.options.mirrors = mirrors.clone();
+                items.push(item);
+            }
+        }
+        Ok(items)
+    }
+}
+
+/// The downloads from the job file at `path`.
+pub fn read_job_downloads(path: &str) -> Result<Vec<DownloadItem>> {
+    load_job(path)?
+        .downloads()
+        .context(format!("Invalid job file '{}'", path))
+}
+
+/// The `--job` path on a command line, if any.
+pub fn job_path(args: &[String]) -> Option<String> {
+    let mut iter = args.iter().take_while(|a| *a != "--");
+    while let Some(arg) = iter.next() {
+        if arg == "--job" {
+            return iter.next().cloned();
+        }
+        if let Some(path) = arg.strip_prefix("--job=") {
+            return Some(path.to_string());
+        }
+    }
+    None
+}
+
+/// Inserts a `--job` file's settings at `insert_at`, ahead of the command
+/// line, so explicit flags still override the job.
+pub fn args_with_job(mut args: Vec<String>, insert_at: usize) -> Result<Vec<String>> {
+    let Some(path) = job_path(&args) else {
+        return Ok(args);
+    };
+    let settings = load_job(&path)?
+        .settings_args()
+        .context(format!("Invalid job file '{}'", path))?;
+    let insert_at = args.len().min(insert_at);
+    args.splice(insert_at..insert_at, settings);
+    Ok(args)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_job_spec() {
+        let spec: JobSpec = serde_yaml_ng::from_str(
+            r#"
+settings:
+  parallel: 4
+  quiet: true
+  atomic: false
+  header: ["Accept: */*", "X-Team: data"]
+downloads:
+  - url: https://example.com/a.iso
+    out: debian.iso
+    checksum: sha-256=abc
+    max-speed: 1.5M
+  - urls: [https://example.com/b.bin, https://example.com/c.bin]
+    headers: ["Authorization: Bearer t"]
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            spec.settings_args().unwrap(),
+            vec![
+                "--header=Accept: */*",
+                "--header=X-Team: data",
+                "--parallel=4",
+                "--quiet"
+            ]
+        );
+        let items = spec.downloads().unwrap();
+        assert_eq!(items.len(), 3);
+        assert_eq!(items[0].options.out.as_deref(), Some("debian.iso"));
+        assert_eq!(items[0].options.max_speed.as_deref(), Some("1572864"));
+        assert_eq!(items[2].url, "https://example.com/c.bin");
+        assert_eq!(items[2].options.headers, vec!["Authorization: Bearer t"]);
+    }
+
+    #[test]
+    fn test_job_spec_errors() {
+        let err = serde_yaml_ng::from_str::<JobSpec>("downloads:\n  - url: x\n    outt: y\n")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("unknown field `outt`") && err.contains("line 3"));
+
+        let spec: JobSpec = serde_json::from_str(r#"{"downloads": [{"out": "a"}]}"#).unwrap();
+        let err = spec.downloads().err().unwrap().to_string();
+        assert_eq!(err, "downloads[0]: needs a url or urls");
+
+        let spec: JobSpec = serde_json::from_str(
+            r#"{"downloads": [{"urls": ["https://a", "https://b"], "out": "x"}]}"#,
+        )
+        .unwrap();
+        assert!(spec.downloads().is_err());
+    }
+
+    #[test]
+    fn test_job_path() {
+        let args = |a: &[&str]| a.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            job_path(&args(&["dlrs", "get", "--job", "nightly.yaml"])).as_deref(),
+            Some("nightly.yaml")
+        );
+        assert_eq!(
+            job_path(&args(&["dlrs", "get", "--job=a.json", "-q"])).as_deref(),
+            Some("a.json")
+        );
+        assert_eq!(job_path(&args(&["dlrs", "get", "--", "--job"])), None);
+    }
+}
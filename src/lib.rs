@@ -0,0 +1,264 @@
+mod app;
+mod cache;
+mod cli;
+mod config;
+mod dns;
+mod engine;
+mod error;
+mod input;
+mod job;
+mod listing;
+mod lock;
+mod logging;
+mod manifest;
+mod menu;
+#[cfg(feature = "metrics")]
+mod metrics;
+mod profile;
+mod progress;
+mod schedule;
+mod session;
+#[cfg(test)]
+mod test_util;
+mod tuning;
+mod usage;
+mod utils;
+mod verify;
+
+pub use crate::cli::{GetArgs, OverwritePolicy};
+pub use crate::engine::DownloadOutcome;
+pub use crate::error::DlrsError;
+pub use crate::progress::{ProgressCallback, ProgressUpdate};
+
+/// The `dlrs` binary's entry point; not part of the library API.
+#[doc(hidden)]
+pub use crate::app::run;
+
+use crate::engine::{DownloadItem, download_file, http_request, probe_client};
+use crate::utils::{redact_url, validate_url};
+use anyhow::{Context, Result};
+use clap::Parser;
+use reqwest::Method;
+use tokio_util::sync::CancellationToken;
+
+/// What the library's settings are parsed from: never the host program's
+/// own command line.
+const NO_ARGS: [&str; 1] = ["dlrs"];
+
+/// Largest body `download_to_bytes` accepts unless told otherwise.
+pub const DEFAULT_MAX_SIZE: u64 = 16 * 1024 * 1024;
+
+/// Settings for the library API.
+#[derive(Clone)]
+pub struct DownloadOptions {
+    /// The same settings as `dlrs get`; TLS, `--header`, `--user-agent` and
+    /// `--connect-timeout` apply to in-memory downloads too. The library
+    /// never prompts, whatever `non_interactive` says.
+    pub config: GetArgs,
+    /// `download_to_bytes` fails rather than buffer more than this many bytes.
+    pub max_size: u64,
+    /// Receives `download`'s progress instead of dlrs printing any; see
+    /// `ProgressCallback` for when and from where it's called.
+    pub on_progress: Option<ProgressCallback>,
+}
+
+impl Default for DownloadOptions {
+    fn default() -> Self {
+        DownloadOptions {
+            config: GetArgs {
+                non_interactive: true,
+                ..GetArgs::parse_from(NO_ARGS)
+            },
+            max_size: DEFAULT_MAX_SIZE,
+            on_progress: None,
+        }
+    }
+}
+
+impl std::fmt::Debug for DownloadOptions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DownloadOptions")
+            .field("config", &self.config)
+            .field("max_size", &self.max_size)
+            .field("on_progress", &self.on_progress.is_some())
+            .finish()
+    }
+}
+
+/// Downloads `url` into `target_dir` with aria2c, as `dlrs get` would.
+pub async fn download(
+    url: &str,
+    target_dir: &str,
+    options: &DownloadOptions,
+) -> Result<DownloadOutcome> {
+    let url = validate_url(url)?;
+    options.config.check_scheme(&url)?;
+    let mut item = DownloadItem::new(url);
+    // An overwrite prompt would block the host program on its stdin
+    let config = GetArgs {
+        non_interactive: true,
+        ..options.config.clone()
+    };
+    download_file(
+        &mut item,
+        target_dir,
+        &config,
+        None,
+        None,
+        None,
+        options.on_progress.as_ref(),
+        CancellationToken::new(),
+    )
+    .await
+}
+
+/// Downloads an HTTP(S) URL into memory over a single connection, for small
+/// resources such as manifests or config files. Fails with
+/// `DlrsError::TooLarge` as soon as the body is known to exceed
+/// `options.max_size`, whether from `Content-Length` or while streaming.
+pub async fn download_to_bytes(url: &str, options: &DownloadOptions) -> Result<Vec<u8>> {
+    let url = validate_url(url)?;
+    options.config.check_scheme(&url)?;
+    if !url.starts_with("http://") && !url.starts_with("https://") {
+        anyhow::bail!(
+            "In-memory downloads only support HTTP(S) URLs, not {}",
+            redact_url(&url)
+        );
+    }
+    let config = &options.config;
+    let too_large = || DlrsError::TooLarge {
+        limit: options.max_size,
+    };
+
+    let mut resp = http_request(
+        &probe_client(config)?,
+        Method::GET,
+        &url,
+        config.user_agent.as_deref(),
+        &config.headers,
+    )
+    .send()
+    .await
+    .and_then(|r| r.error_for_status())
+    .context(format!("Failed to fetch {}", redact_url(&url)))?;
+    if resp
+        .content_length()
+        .is_some_and(|len| len > options.max_size)
+    {
+        return Err(too_large().into());
+    }
+
+    let mut body = Vec::new();
+    while let Some(chunk) = resp.chunk().await? {
+        if (body.len() + chunk.len()) as u64 > options.max_size {
+            return Err(too_large().into());
+        }
+        body.extend_from_slice(&chunk);
+    }
+    Ok(body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::test_dir;
+    use std::sync::{Arc, Mutex};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    #[tokio::test]
+    async fn test_download_to_bytes() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let mut buf = vec![0u8; 4096];
+                let _ = socket.read(&mut buf).await;
+                let _ = socket
+                    .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 8\r\n\r\nmanifest")
+                    .await;
+            }
+        });
+        let url = format!("http://{}/manifest.json", addr);
+
+        // TLS only by default, as on the command line
+        let err = download_to_bytes(&url, &DownloadOptions::default())
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<DlrsError>(),
+            Some(DlrsError::InvalidUrl { .. })
+        ));
+
+        let plain = DownloadOptions {
+            config: GetArgs::parse_from(["dlrs", "--allowed-schemes", "http,ftp"]),
+            ..DownloadOptions::default()
+        };
+        let body = download_to_bytes(&url, &plain).await.unwrap();
+        assert_eq!(body, b"manifest");
+
+        let small = DownloadOptions {
+            max_size: 4,
+            ..plain.clone()
+        };
+        let err = download_to_bytes(&url, &small).await.unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<DlrsError>(),
+            Some(DlrsError::TooLarge { limit: 4 })
+        ));
+        assert!(
+            download_to_bytes("ftp://example.com/a", &plain)
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_download_reports_progress() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let mut buf = vec![0u8; 4096];
+                let _ = socket.read(&mut buf).await;
+                let _ = socket
+                    .write_all(b"HTTP/1.1 206 Partial Content\r\nContent-Range: bytes 0-3/8\r\nContent-Disposition: attachment; filename=\"part.bin\"\r\nContent-Length: 4\r\n\r\nabcd")
+                    .await;
+            }
+        });
+        let dir = test_dir("callback");
+
+        let updates = Arc::new(Mutex::new(Vec::new()));
+        let seen = updates.clone();
+        let options = DownloadOptions {
+            config: GetArgs::parse_from(["dlrs", "--range", "0-3", "--allowed-schemes", "http"]),
+            on_progress: Some(Arc::new(move |u| {
+                seen.lock()
+                    .unwrap()
+                    .push((u.filename.to_string(), u.downloaded, u.total));
+            })),
+            ..DownloadOptions::default()
+        };
+        download(
+            &format!("http://{}/get?id=1", addr),
+            &dir.to_string_lossy(),
+            &options,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            updates.lock().unwrap().last(),
+            Some(&("part.bin".to_string(), 4, 4))
+        );
+
+        // The file exists now; the library goes ahead rather than prompt
+        assert!(DownloadOptions::default().config.non_interactive);
+        download(
+            &format!("http://{}/get?id=1", addr),
+            &dir.to_string_lossy(),
+            &options,
+        )
+        .await
+        .unwrap();
+    }
+}
@@ -0,0 +1,211 @@
+use crate::cli::GetArgs;
+use crate::engine::probe_client;
+use anyhow::{Context, Result};
+use regex::Regex;
+use std::collections::HashSet;
+use std::sync::LazyLock;
+use std::time::Duration;
+use url::Url;
+
+static HREF_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"(?i)<a\s[^>]*?href\s*=\s*["']([^"']+)["']"#).expect("Invalid regex")
+});
+
+/// A URL whose path ends in `/` names a directory listing.
+pub fn is_directory_url(raw_url: &str) -> bool {
+    Url::parse(raw_url)
+        .is_ok_and(|u| matches!(u.scheme(), "http" | "https" | "ftp") && u.path().ends_with('/'))
+}
+
+/// Expands a directory URL into the files below it, descending at most
+/// `--max-depth` levels of subdirectories.
+pub async fn expand_directory(raw_url: &str, config: &GetArgs) -> Result<Vec<String>> {
+    let root = Url::parse(raw_url)?;
+    if root.scheme() == "ftp" {
+        let config = config.clone();
+        return tokio::task::spawn_blocking(move || list_ftp(&root, &config)).await?;
+    }
+
+    let client = probe_client(config)?;
+    let mut files = Vec::new();
+    let mut seen = HashSet::new();
+    let mut queue = vec![(root, 0)];
+
+    while let Some((dir, depth)) = queue.pop() {
+        if !seen.insert(dir.clone()) {
+            continue;
+        }
+        let mut req = client.get(dir.clone()).header(
+            "User-Agent",
+            config
+                .user_agent
+                .as_deref()
+                .unwrap_or(concat!("dlrs/", env!("CARGO_PKG_VERSION"))),
+        );
+        for header in &config.headers {
+            if let Some((name, value)) = header.split_once(':') {
+                req = req.header(name.trim(), value.trim());
+            }
+        }
+        let html = req
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .context(format!("Failed to list {}", dir))?
+            .text()
+            .await?;
+
+        let (found, subdirs) = index_links(&dir, &html);
+        files.extend(found.into_iter().map(String::from));
+        if depth < config.max_depth {
+            queue.extend(subdirs.into_iter().map(|d| (d, depth + 1)));
+        }
+    }
+
+    files.sort();
+    files.dedup();
+    Ok(files)
+}
+
+/// Links in an HTML index that point below `base`, split into files and
+/// subdirectories. Parent links, sort links (`?C=N;O=D`) and other hosts
+/// are skipped.
+fn index_links(base: &Url, html: &str) -> (Vec<Url>, Vec<Url>) {
+    let mut files = Vec::new();
+    let mut dirs = Vec::new();
+
+    for caps in HREF_RE.captures_iter(html) {
+        let href = caps[1].trim();
+        if href.starts_with(['?', '#']) {
+            continue;
+        }
+        let Ok(mut link) = base.join(href) else {
+            continue;
+        };
+        link.set_fragment(None);
+        if link.query().is_some()
+            || link.scheme() != base.scheme()
+            || link.host_str() != base.host_str()
+            || link.port_or_known_default() != base.port_or_known_default()
+            || link.path() == base.path()
+            || !link.path().starts_with(base.path())
+        {
+            continue;
+        }
+
+        let list = if link.path().ends_with('/') {
+            &mut dirs
+        } else {
+            &mut files
+        };
+        if !list.contains(&link) {
+            list.push(link);
+        }
+    }
+
+    (files, dirs)
+}
+
+fn list_ftp(root: &Url, config: &GetArgs) -> Result<Vec<String>> {
+    use suppaftp::list::File;
+    use suppaftp::{FtpStream, Mode};
+
+    let host = root.host_str().context("FTP URL must contain a host")?;
+    let port = root.port().unwrap_or(21);
+    let mut ftp =
+        FtpStream::connect((host, port)).context(format!("Failed to connect to {}", host))?;
+    ftp.get_ref()
+        .set_read_timeout(Some(Duration::from_secs(config.connect_timeout)))?;
+
+    // Same precedence as the download itself: URL credentials, then flags
+    let (user, password) = if !root.username().is_empty() {
+        (
+            percent_encoding::percent_decode_str(root.username())
+                .decode_utf8_lossy()
+                .to_string(),
+            percent_encoding::percent_decode_str(root.password().unwrap_or(""))
+                .decode_utf8_lossy()
+                .to_string(),
+        )
+    } else {
+        (
+            config.ftp_user.clone().unwrap_or("anonymous".to_string()),
+            config.ftp_password.clone().unwrap_or_default(),
+        )
+    };
+    ftp.login(&user, &password)
+        .context(format!("FTP login to {} failed", host))?;
+    if config.ftp_active {
+        ftp.set_mode(Mode::Active);
+    }
+
+    let mut files = Vec::new();
+    let mut queue = vec![(root.clone(), 0)];
+    while let Some((dir, depth)) = queue.pop() {
+        let path = percent_encoding::percent_decode_str(dir.path()).decode_utf8_lossy();
+        let lines = ftp
+            .list(Some(&path))
+            .context(format!("Failed to list {}", dir))?;
+
+        for entry in lines.iter().filter_map(|l| File::try_from(l.as_str()).ok()) {
+            if entry.name() == "." || entry.name() == ".." {
+                continue;
+            }
+            if entry.is_directory() {
+                if depth < config.max_depth
+                    && let Ok(sub) = dir.join(&format!("{}/", entry.name()))
+                {
+                    queue.push((sub, depth + 1));
+                }
+            } else if entry.is_file()
+                && let Ok(file) = dir.join(entry.name())
+            {
+                files.push(file.to_string());
+            }
+        }
+    }
+    let _ = ftp.quit();
+
+    files.sort();
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_index_links() {
+        let base = Url::parse("https://example.com/pub/iso/").unwrap();
+        let html = r#"
+<a href="?C=N;O=D">Name</a>
+<a href="../">Parent Directory</a>
+<a href="/pub/">pub</a>
+<a href="debian-12.iso">debian-12.iso</a>
+<a HREF='my%20notes.txt'>notes</a>
+<a href="old/">old/</a>
+<a href="https://other.example.com/pub/iso/x.iso">mirror</a>
+<a href="debian-12.iso#top">again</a>
+"#;
+        let (files, dirs) = index_links(&base, html);
+        assert_eq!(
+            files.iter().map(Url::as_str).collect::<Vec<_>>(),
+            vec![
+                "https://example.com/pub/iso/debian-12.iso",
+                "https://example.com/pub/iso/my%20notes.txt"
+            ]
+        );
+        assert_eq!(
+            dirs.iter().map(Url::as_str).collect::<Vec<_>>(),
+            vec!["https://example.com/pub/iso/old/"]
+        );
+    }
+
+    #[test]
+    fn test_is_directory_url() {
+        assert!(is_directory_url("https://example.com/pub/"));
+        assert!(is_directory_url("ftp://example.com/pub/"));
+        assert!(!is_directory_url("https://example.com/pub/a.iso"));
+        assert!(!is_directory_url("magnet:?xt=urn:btih:abc"));
+    }
+}
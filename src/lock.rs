@@ -0,0 +1,123 @@
+use crate::error::DlrsError;
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+
+/// How often `--wait-lock` checks whether the other download is done.
+const LOCK_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// An advisory `flock` on `<file>.dlrs-lock`, held while a download writes
+/// `<file>`, so two dlrs runs resolving to the same name can't interleave
+/// their writes; `.dlrs-session` is guarded the same way. The lock file is
+/// removed when the guard drops. Without
+/// `flock` (non-Unix systems) nothing is locked.
+pub struct DownloadLock {
+    #[cfg(unix)]
+    _file: std::fs::File,
+    #[cfg(unix)]
+    path: PathBuf,
+}
+
+impl DownloadLock {
+    /// Takes the lock for `file_path`. When another process (or another
+    /// download in this one) holds it, fails with `DlrsError::Locked`, or with
+    /// `wait` polls until it's released or `cancel_token` fires.
+    pub async fn acquire(
+        file_path: &str,
+        wait: bool,
+        cancel_token: &CancellationToken,
+        mut on_wait: impl FnMut(),
+    ) -> Result<Self> {
+        let path = PathBuf::from(format!("{}.dlrs-lock", file_path));
+        let mut waited = false;
+        loop {
+            if let Some(lock) = Self::try_acquire(&path, false)? {
+                return Ok(lock);
+            }
+            if !wait {
+                return Err(DlrsError::Locked {
+                    file: file_path.to_string(),
+                }
+                .into());
+            }
+            if !waited {
+                on_wait();
+                waited = true;
+            }
+            tokio::select! {
+                _ = tokio::time::sleep(LOCK_POLL_INTERVAL) => {}
+                _ = cancel_token.cancelled() => return Err(DlrsError::Cancelled.into()),
+            }
+        }
+    }
+
+    /// Takes the lock for `file_path`, blocking the thread until any other
+    /// holder lets go. For short read-modify-write cycles such as the
+    /// session file's, not for downloads.
+    pub fn acquire_blocking(file_path: &str) -> Result<Self> {
+        let path = PathBuf::from(format!("{}.dlrs-lock", file_path));
+        loop {
+            if let Some(lock) = Self::try_acquire(&path, true)? {
+                return Ok(lock);
+            }
+        }
+    }
+
+    /// Locks `path`; with `block`, waits in `flock` rather than returning
+    /// `None` when it's held.
+    #[cfg(unix)]
+    fn try_acquire(path: &Path, block: bool) -> Result<Option<Self>> {
+        use std::os::unix::fs::MetadataExt;
+        use std::os::unix::io::AsRawFd;
+
+        loop {
+            let file = std::fs::OpenOptions::new()
+                .create(true)
+                .truncate(false)
+                .write(true)
+                .open(path)
+                .context(format!("Failed to create lock file {:?}", path))?;
+            let flags = if block {
+                libc::LOCK_EX
+            } else {
+                libc::LOCK_EX | libc::LOCK_NB
+            };
+            if unsafe { libc::flock(file.as_raw_fd(), flags) } != 0 {
+                let err = std::io::Error::last_os_error();
+                // A blocking flock can be interrupted by a signal; try again
+                if matches!(
+                    err.kind(),
+                    std::io::ErrorKind::WouldBlock | std::io::ErrorKind::Interrupted
+                ) {
+                    return Ok(None);
+                }
+                return Err(err).context(format!("Failed to lock {:?}", path));
+            }
+            // The holder we waited on may have removed the file before we
+            // locked it; a lock on an unlinked file protects nothing, so
+            // start over with the current one
+            let current = std::fs::metadata(path).map(|m| m.ino()).ok();
+            if current == Some(file.metadata()?.ino()) {
+                return Ok(Some(DownloadLock {
+                    _file: file,
+                    path: path.to_path_buf(),
+                }));
+            }
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn try_acquire(_path: &Path, _block: bool) -> Result<Option<Self>> {
+        Ok(Some(DownloadLock {}))
+    }
+}
+
+#[cfg(unix)]
+impl Drop for DownloadLock {
+    fn drop(&mut self) {
+        // Still locked, so nobody else can be using this file yet; waiters
+        // notice it's gone and lock a fresh one
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
@@ -0,0 +1,83 @@
+use crate::progress::MultiProgress;
+#[cfg(feature = "tui")]
+use colored::Colorize;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static LOGS_ENABLED: AtomicBool = AtomicBool::new(true);
+
+/// Silences `log_info`, `log_success` and `log_warning` for `--no-logs`.
+/// Errors are still printed.
+pub fn disable_logs() {
+    LOGS_ENABLED.store(false, Ordering::Relaxed);
+}
+
+fn logs_enabled() -> bool {
+    LOGS_ENABLED.load(Ordering::Relaxed)
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Color {
+    Cyan,
+    Green,
+    Yellow,
+    Red,
+}
+
+/// Colors `text` for the terminal; builds without the `tui` feature
+/// return it unchanged.
+#[cfg(feature = "tui")]
+pub fn paint(text: &str, color: Color) -> String {
+    let color = match color {
+        Color::Cyan => colored::Color::Cyan,
+        Color::Green => colored::Color::Green,
+        Color::Yellow => colored::Color::Yellow,
+        Color::Red => colored::Color::Red,
+    };
+    text.color(color).to_string()
+}
+
+#[cfg(not(feature = "tui"))]
+pub fn paint(text: &str, _color: Color) -> String {
+    text.to_string()
+}
+
+pub fn log_info(msg: &str) {
+    if !logs_enabled() {
+        return;
+    }
+    println!("{} {}", paint("[INFO]", Color::Cyan), msg);
+}
+
+pub fn log_success(msg: &str) {
+    if !logs_enabled() {
+        return;
+    }
+    println!("{} {}", paint("[SUCCESS]", Color::Green), msg);
+}
+
+pub fn log_warning(msg: &str) {
+    if !logs_enabled() {
+        return;
+    }
+    println!("{} {}", paint("[WARNING]", Color::Yellow), msg);
+}
+
+pub fn log_error(msg: &str) {
+    eprintln!("{} {}", paint("[ERROR]", Color::Red), msg);
+}
+
+/// Logs info without tearing through active progress bars.
+pub fn log_info_with(mp: Option<&MultiProgress>, msg: &str) {
+    match mp {
+        Some(m) => m.suspend(|| log_info(msg)),
+        None => log_info(msg),
+    }
+}
+
+/// Logs a warning without tearing through active progress bars.
+pub fn log_warning_with(mp: Option<&MultiProgress>, msg: &str) {
+    match mp {
+        Some(m) => m.suspend(|| log_warning(msg)),
+        None => log_warning(msg),
+    }
+}
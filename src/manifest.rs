@@ -0,0 +1,165 @@
+use anyhow::{Context, Result};
+use serde_json::Value;
+use std::path::Path;
+use url::Url;
+
+/// Strings found at a dotted `path` (`files.url`, `data.0.href`) in `json`.
+/// A key applied to an array is applied to every element, so `files.url`
+/// collects the `url` of each entry in `files`; a number indexes into an
+/// array. An empty path or `.` means the document itself.
+pub fn manifest_urls(json: &Value, path: &str) -> Vec<String> {
+    let mut current = vec![json];
+    for segment in path.split('.').filter(|s| !s.is_empty()) {
+        let mut next = Vec::new();
+        for value in current {
+            collect_segment(value, segment, &mut next);
+        }
+        current = next;
+    }
+
+    let mut urls = Vec::new();
+    for value in current {
+        match value {
+            Value::String(s) => urls.push(s.clone()),
+            Value::Array(items) => {
+                urls.extend(items.iter().filter_map(Value::as_str).map(String::from))
+            }
+            _ => {}
+        }
+    }
+    urls
+}
+
+fn collect_segment<'a>(value: &'a Value, segment: &str, out: &mut Vec<&'a Value>) {
+    match value {
+        Value::Object(map) => out.extend(map.get(segment)),
+        Value::Array(items) => match segment.parse::<usize>() {
+            Ok(index) => out.extend(items.get(index)),
+            Err(_) => {
+                for item in items {
+                    collect_segment(item, segment, out);
+                }
+            }
+        },
+        _ => {}
+    }
+}
+
+/// Largest file `read_manifest` parses; a list of links is far smaller, so
+/// anything bigger is some other download.
+const MAX_MANIFEST_SIZE: u64 = crate::DEFAULT_MAX_SIZE;
+
+/// Whether a download can be a manifest: served as JSON, or named `.json`
+/// (servers often send those as `text/plain` or `application/octet-stream`).
+pub fn is_json(file_path: &str, content_type: Option<&str>) -> bool {
+    let media_type = content_type
+        .and_then(|t| t.split(';').next())
+        .map(|t| t.trim().to_ascii_lowercase());
+    media_type.is_some_and(|t| t == "application/json" || t.ends_with("+json"))
+        || Path::new(file_path)
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("json"))
+}
+
+/// Reads the downloaded manifest at `file` for `--follow-manifest`, with
+/// URLs relative to `base_url`, the one it came from, made absolute.
+pub fn read_manifest(file: &Path, path: &str, base_url: &str) -> Result<Vec<String>> {
+    let size = std::fs::metadata(file)
+        .context(format!("Failed to read manifest {}", file.display()))?
+        .len();
+    if size > MAX_MANIFEST_SIZE {
+        anyhow::bail!(
+            "{} is {} bytes, too large for a manifest",
+            file.display(),
+            size
+        );
+    }
+    let contents = std::fs::read_to_string(file)
+        .context(format!("Failed to read manifest {}", file.display()))?;
+    let json: Value = serde_json::from_str(&contents)
+        .context(format!("{} is not a JSON manifest", file.display()))?;
+    Ok(resolve_urls(base_url, manifest_urls(&json, path)))
+}
+
+/// `urls` joined onto `base_url` the way a browser resolves links; left
+/// as they are when the base doesn't parse.
+fn resolve_urls(base_url: &str, urls: Vec<String>) -> Vec<String> {
+    let Ok(base) = Url::parse(base_url) else {
+        return urls;
+    };
+    urls.into_iter()
+        .map(|u| base.join(&u).map_or(u, String::from))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_manifest_urls() {
+        let json: Value = serde_json::from_str(
+            r#"{
+                "files": [
+                    {"url": "https://example.com/a.bin", "size": 1},
+                    {"url": "https://example.com/b.bin"},
+                    {"name": "no url"}
+                ],
+                "mirrors": ["https://m1.example.com/", "https://m2.example.com/"],
+                "latest": {"href": "https://example.com/latest.bin"}
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            manifest_urls(&json, "files.url"),
+            vec!["https://example.com/a.bin", "https://example.com/b.bin"]
+        );
+        assert_eq!(
+            manifest_urls(&json, "files.1.url"),
+            vec!["https://example.com/b.bin"]
+        );
+        assert_eq!(manifest_urls(&json, "mirrors").len(), 2);
+        assert_eq!(
+            manifest_urls(&json, "latest.href"),
+            vec!["https://example.com/latest.bin"]
+        );
+        assert!(manifest_urls(&json, "files.size").is_empty());
+        assert!(manifest_urls(&json, "missing.url").is_empty());
+
+        let list: Value = serde_json::from_str(r#"["https://example.com/x"]"#).unwrap();
+        assert_eq!(manifest_urls(&list, "."), vec!["https://example.com/x"]);
+    }
+
+    #[test]
+    fn test_is_json() {
+        assert!(is_json("out/list", Some("application/json; charset=utf-8")));
+        assert!(is_json("out/list", Some("application/vnd.api+json")));
+        assert!(is_json("out/list.JSON", Some("text/plain")));
+        assert!(is_json("out/list.json", None));
+        assert!(!is_json("out/image.iso", Some("application/octet-stream")));
+        assert!(!is_json("out/image.iso", None));
+    }
+
+    #[test]
+    fn test_resolve_urls() {
+        let urls = [
+            "b.bin",
+            "/root.bin",
+            "../up.bin",
+            "https://other.example.com/c.bin",
+        ]
+        .map(String::from)
+        .to_vec();
+        assert_eq!(
+            resolve_urls("https://example.com/release/manifest.json", urls.clone()),
+            vec![
+                "https://example.com/release/b.bin",
+                "https://example.com/root.bin",
+                "https://example.com/up.bin",
+                "https://other.example.com/c.bin"
+            ]
+        );
+        assert_eq!(resolve_urls("not a url", urls.clone()), urls);
+    }
+}
@@ -0,0 +1,143 @@
+use crate::progress::MultiProgress;
+use std::io::{BufRead, Write};
+use std::sync::Arc;
+use std::sync::Mutex;
+use tokio_util::sync::CancellationToken;
+
+/// Downloads the `--cancel-menu` can stop, each holding a child of the
+/// batch's cancellation token so cancelling one leaves the rest running.
+#[derive(Default)]
+pub struct ActiveDownloads {
+    next_id: Mutex<usize>,
+    entries: Mutex<Vec<(usize, String, CancellationToken)>>,
+}
+
+impl ActiveDownloads {
+    /// Adds a running download; pass the returned id to `remove` once it ends.
+    pub fn add(&self, label: String, token: CancellationToken) -> usize {
+        let mut next_id = self.next_id.lock().unwrap();
+        *next_id += 1;
+        self.entries.lock().unwrap().push((*next_id, label, token));
+        *next_id
+    }
+
+    pub fn remove(&self, id: usize) {
+        self.entries.lock().unwrap().retain(|(i, _, _)| *i != id);
+    }
+
+    /// The running downloads' ids and labels, in the order they started.
+    pub fn labels(&self) -> Vec<(usize, String)> {
+        self.entries
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(id, label, _)| (*id, label.clone()))
+            .collect()
+    }
+
+    /// Cancels the download with `id`; false when it has already ended.
+    pub fn cancel(&self, id: usize) -> bool {
+        let entries = self.entries.lock().unwrap();
+        match entries.iter().find(|(i, _, _)| *i == id) {
+            Some((_, _, token)) => {
+                token.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Reads the terminal on a background thread: Enter lists the running
+/// downloads and asks which one to cancel. The thread lives as long as the
+/// process, since a blocked stdin read can't be interrupted.
+pub fn spawn_cancel_menu(active: Arc<ActiveDownloads>, mp: Arc<Option<MultiProgress>>) {
+    std::thread::spawn(move || {
+        let stdin = std::io::stdin();
+        let mut input = stdin.lock();
+        let mut line = String::new();
+        loop {
+            line.clear();
+            if input.read_line(&mut line).unwrap_or(0) == 0 {
+                return;
+            }
+            let show = || {
+                let listed = active.labels();
+                if listed.is_empty() {
+                    eprintln!("No downloads running");
+                    return listed;
+                }
+                for (i, (_, label)) in listed.iter().enumerate() {
+                    eprintln!("  {}) {}", i + 1, label);
+                }
+                eprint!("Cancel which download (number, Enter to keep all)? ");
+                let _ = std::io::stderr().flush();
+                listed
+            };
+            let listed = match mp.as_ref() {
+                Some(m) => m.suspend(show),
+                None => show(),
+            };
+            if listed.is_empty() {
+                continue;
+            }
+            // Outside suspend, which would stop every progress bar until
+            // the answer comes
+            line.clear();
+            if input.read_line(&mut line).unwrap_or(0) == 0 {
+                return;
+            }
+            if let Some(n) = parse_choice(&line, listed.len()) {
+                // By id, since the list may have changed while the user chose
+                let (id, label) = &listed[n - 1];
+                if active.cancel(*id) {
+                    eprintln!("Cancelling {}", label);
+                } else {
+                    eprintln!("{} has already finished", label);
+                }
+            }
+        }
+    });
+}
+
+/// A menu answer: a listed number, or `None` to cancel nothing.
+fn parse_choice(answer: &str, count: usize) -> Option<usize> {
+    answer
+        .trim()
+        .parse()
+        .ok()
+        .filter(|n| (1..=count).contains(n))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_active_downloads() {
+        let active = ActiveDownloads::default();
+        let a = CancellationToken::new();
+        let b = CancellationToken::new();
+        let first = active.add("a.iso".to_string(), a.clone());
+        let second = active.add("b.iso".to_string(), b.clone());
+        assert_eq!(
+            active.labels(),
+            vec![(first, "a.iso".to_string()), (second, "b.iso".to_string())]
+        );
+
+        // b moves up to the first place in the list, but its id stays
+        active.remove(first);
+        assert!(!active.cancel(first));
+        assert!(!a.is_cancelled());
+        assert!(active.cancel(second));
+        assert!(b.is_cancelled());
+    }
+
+    #[test]
+    fn test_parse_choice() {
+        assert_eq!(parse_choice("2\n", 3), Some(2));
+        assert_eq!(parse_choice("\n", 3), None);
+        assert_eq!(parse_choice("4\n", 3), None);
+        assert_eq!(parse_choice("0\n", 3), None);
+    }
+}
@@ -0,0 +1,177 @@
+use anyhow::{Context, Result};
+use std::sync::atomic::{AtomicU64, Ordering};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+/// The counters `/metrics` serves. The process-wide set is `GLOBAL`;
+/// tests build their own so downloads elsewhere can't move the numbers.
+#[derive(Default)]
+struct Metrics {
+    started: AtomicU64,
+    completed: AtomicU64,
+    failed: AtomicU64,
+    cancelled: AtomicU64,
+    active: AtomicU64,
+    bytes: AtomicU64,
+}
+
+/// How a download ended, for the counters.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Ending {
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+static GLOBAL: Metrics = Metrics {
+    started: AtomicU64::new(0),
+    completed: AtomicU64::new(0),
+    failed: AtomicU64::new(0),
+    cancelled: AtomicU64::new(0),
+    active: AtomicU64::new(0),
+    bytes: AtomicU64::new(0),
+};
+
+impl Metrics {
+    fn download_started(&self) {
+        self.started.fetch_add(1, Ordering::Relaxed);
+        self.active.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn download_finished(&self, ending: Ending) {
+        self.active.fetch_sub(1, Ordering::Relaxed);
+        let counter = match ending {
+            Ending::Completed => &self.completed,
+            Ending::Failed => &self.failed,
+            Ending::Cancelled => &self.cancelled,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn add_bytes(&self, bytes: u64) {
+        self.bytes.fetch_add(bytes, Ordering::Relaxed);
+    }
+}
+
+pub fn download_started() {
+    GLOBAL.download_started();
+}
+
+pub fn download_finished(ending: Ending) {
+    GLOBAL.download_finished(ending);
+}
+
+pub fn add_bytes(bytes: u64) {
+    GLOBAL.add_bytes(bytes);
+}
+
+/// Binds `addr` and serves the counters in Prometheus text format on
+/// `/metrics` for as long as the process runs.
+pub async fn serve(addr: &str) -> Result<()> {
+    let listener = TcpListener::bind(addr)
+        .await
+        .context(format!("Failed to listen on {} for metrics", addr))?;
+
+    tokio::spawn(async move {
+        while let Ok((mut stream, _)) = listener.accept().await {
+            tokio::spawn(async move {
+                let mut buf = [0u8; 1024];
+                let Ok(n) = stream.read(&mut buf).await else {
+                    return;
+                };
+                let request = String::from_utf8_lossy(&buf[..n]);
+                let path = request.split_whitespace().nth(1).unwrap_or("");
+                let response = if path == "/metrics" {
+                    let body = render(&GLOBAL);
+                    format!(
+                        "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                        body.len(),
+                        body
+                    )
+                } else {
+                    "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                        .to_string()
+                };
+                let _ = stream.write_all(response.as_bytes()).await;
+            });
+        }
+    });
+
+    Ok(())
+}
+
+fn render(metrics: &Metrics) -> String {
+    let counters = [
+        (
+            "dlrs_downloads_started_total",
+            "counter",
+            "Downloads started",
+            &metrics.started,
+        ),
+        (
+            "dlrs_downloads_completed_total",
+            "counter",
+            "Downloads that finished successfully",
+            &metrics.completed,
+        ),
+        (
+            "dlrs_downloads_failed_total",
+            "counter",
+            "Downloads that failed",
+            &metrics.failed,
+        ),
+        (
+            "dlrs_downloads_cancelled_total",
+            "counter",
+            "Downloads cancelled before they finished",
+            &metrics.cancelled,
+        ),
+        (
+            "dlrs_downloads_active",
+            "gauge",
+            "Downloads currently running",
+            &metrics.active,
+        ),
+        (
+            "dlrs_downloaded_bytes_total",
+            "counter",
+            "Bytes received across all downloads",
+            &metrics.bytes,
+        ),
+    ];
+
+    let mut out = String::new();
+    for (name, kind, help, value) in counters {
+        out.push_str(&format!(
+            "# HELP {name} {help}\n# TYPE {name} {kind}\n{name} {}\n",
+            value.load(Ordering::Relaxed)
+        ));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let metrics = Metrics::default();
+        metrics.download_started();
+        metrics.download_started();
+        metrics.add_bytes(1024);
+        metrics.download_started();
+        metrics.download_finished(Ending::Completed);
+        metrics.download_finished(Ending::Cancelled);
+
+        let text = render(&metrics);
+        assert!(text.contains("# TYPE dlrs_downloads_started_total counter\n"));
+        assert!(text.contains("# TYPE dlrs_downloads_active gauge\n"));
+        assert!(text.contains("\ndlrs_downloads_started_total 3\n"));
+        assert!(text.contains("\ndlrs_downloads_completed_total 1\n"));
+        assert!(text.contains("\ndlrs_downloads_failed_total 0\n"));
+        assert!(text.contains("\ndlrs_downloads_cancelled_total 1\n"));
+        assert!(text.contains("\ndlrs_downloads_active 1\n"));
+        assert!(text.contains("\ndlrs_downloaded_bytes_total 1024\n"));
+    }
+}
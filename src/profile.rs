@@ -0,0 +1,145 @@
+use crate::cli::{FileAllocation, GetArgs};
+use clap::ArgMatches;
+use clap::ValueEnum;
+use clap::parser::ValueSource;
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Profile {
+    /// Many connections, large splits and a big disk cache
+    Fast,
+    /// One connection, one split and a rate limit to avoid hammering servers
+    Polite,
+    /// Few connections and patient retries for flaky links
+    Resume,
+    /// Small disk cache and few connections for routers and SBCs
+    LowMemory,
+}
+
+/// Values a profile seeds; `None` leaves dlrs's built-in default.
+#[derive(Default)]
+struct Preset {
+    connections: Option<u32>,
+    split: Option<u32>,
+    min_split_size: Option<&'static str>,
+    disk_cache: Option<&'static str>,
+    file_allocation: Option<FileAllocation>,
+    max_speed: Option<&'static str>,
+    max_tries: Option<u32>,
+    retry_wait: Option<u64>,
+}
+
+impl Profile {
+    fn preset(self) -> Preset {
+        match self {
+            Profile::Fast => Preset {
+                connections: Some(16),
+                split: Some(64),
+                min_split_size: Some("1M"),
+                disk_cache: Some("256M"),
+                ..Preset::default()
+            },
+            Profile::Polite => Preset {
+                connections: Some(1),
+                split: Some(1),
+                max_speed: Some("1M"),
+                retry_wait: Some(30),
+                ..Preset::default()
+            },
+            Profile::Resume => Preset {
+                connections: Some(4),
+                split: Some(4),
+                max_tries: Some(10),
+                retry_wait: Some(20),
+                ..Preset::default()
+            },
+            Profile::LowMemory => Preset {
+                connections: Some(4),
+                split: Some(4),
+                min_split_size: Some("4M"),
+                disk_cache: Some("0"),
+                file_allocation: Some(FileAllocation::None),
+                ..Preset::default()
+            },
+        }
+    }
+}
+
+fn seed<T>(field: &mut Option<T>, value: Option<T>) {
+    if field.is_none() {
+        *field = value;
+    }
+}
+
+/// For options with a clap default, which can't be `None`.
+fn seed_default<T>(field: &mut T, value: Option<T>, matches: &ArgMatches, id: &str) {
+    if let Some(value) = value
+        && matches.value_source(id) == Some(ValueSource::DefaultValue)
+    {
+        *field = value;
+    }
+}
+
+/// Fills options the user didn't set explicitly from the selected profile.
+pub fn apply_profile(cli: &mut GetArgs, matches: &ArgMatches) {
+    let Some(profile) = cli.profile else {
+        return;
+    };
+    let preset = profile.preset();
+
+    seed(&mut cli.connections, preset.connections);
+    seed(&mut cli.split, preset.split);
+    seed(
+        &mut cli.min_split_size,
+        preset.min_split_size.map(str::to_string),
+    );
+    seed(&mut cli.disk_cache, preset.disk_cache.map(str::to_string));
+    seed(&mut cli.file_allocation, preset.file_allocation);
+    seed(&mut cli.max_speed, preset.max_speed.map(str::to_string));
+    seed_default(&mut cli.max_tries, preset.max_tries, matches, "max_tries");
+    seed_default(
+        &mut cli.retry_wait,
+        preset.retry_wait,
+        matches,
+        "retry_wait",
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::{CommandFactory, FromArgMatches};
+
+    fn profiled(args: &[&str]) -> GetArgs {
+        let matches = GetArgs::command().get_matches_from(["dlrs"].iter().chain(args));
+        let mut cli = GetArgs::from_arg_matches(&matches).unwrap();
+        apply_profile(&mut cli, &matches);
+        cli
+    }
+
+    #[test]
+    fn test_apply_profile() {
+        let cli = profiled(&["--profile", "low-memory", "--split", "2", "x"]);
+        assert_eq!(cli.split, Some(2));
+        assert_eq!(cli.connections, Some(4));
+        assert_eq!(cli.disk_cache.as_deref(), Some("0"));
+        assert_eq!(cli.file_allocation, Some(FileAllocation::None));
+
+        let cli = profiled(&["x"]);
+        assert_eq!(cli.connections, None);
+        assert_eq!(cli.max_tries, 5);
+    }
+
+    #[test]
+    fn test_apply_profile_overrides_clap_defaults() {
+        let cli = profiled(&["--profile", "resume", "x"]);
+        assert_eq!(cli.max_tries, 10);
+        assert_eq!(cli.retry_wait, 20);
+
+        let cli = profiled(&["--profile", "resume", "--max-tries", "3", "x"]);
+        assert_eq!(cli.max_tries, 3);
+
+        let cli = profiled(&["--profile", "polite", "--max-speed", "200K", "x"]);
+        assert_eq!(cli.max_speed.as_deref(), Some("200K"));
+        assert_eq!(cli.connections, Some(1));
+    }
+}
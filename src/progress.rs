@@ -0,0 +1,915 @@
+use anyhow::Result;
+#[cfg(feature = "tui")]
+pub use indicatif::{HumanBytes, MultiProgress};
+#[cfg(feature = "tui")]
+use indicatif::{ProgressBar, ProgressStyle};
+use percent_encoding::{AsciiSet, CONTROLS, utf8_percent_encode};
+use std::io::Write;
+use std::sync::Arc;
+#[cfg(feature = "tui")]
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc::{Receiver, SyncSender};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+const PLAIN_INTERVAL: Duration = Duration::from_secs(5);
+/// Window the peak speed is measured over, so a burst of closely spaced
+/// samples can't report an absurd rate.
+const PEAK_WINDOW: Duration = Duration::from_secs(1);
+
+/// Characters escaped in `--progress-format machine` values, so every
+/// line splits cleanly on spaces and the first `=`.
+const MACHINE_ESCAPES: &AsciiSet = &CONTROLS.add(b' ').add(b'%').add(b'=');
+
+/// The `--progress-socket` connection; when set, machine events go here
+/// as JSON instead of to `--progress-fd`.
+static SOCKET: OnceLock<SocketWriter> = OnceLock::new();
+
+/// How long an event may wait on a frontend that stopped reading before
+/// dlrs gives up on it, and how long exiting waits for queued events.
+const SOCKET_WRITE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Events waiting for a slow frontend; once this many are queued, new ones
+/// are dropped rather than holding up the downloads.
+const SOCKET_QUEUE: usize = 1024;
+
+/// Stand-in for indicatif's `MultiProgress` in builds without the `tui`
+/// feature. It has no values, so an `Option<&MultiProgress>` is always
+/// `None` and every caller falls back to plain output.
+#[cfg(not(feature = "tui"))]
+#[derive(Clone)]
+pub enum MultiProgress {}
+
+#[cfg(not(feature = "tui"))]
+impl MultiProgress {
+    pub fn println<I: AsRef<str>>(&self, _msg: I) -> std::io::Result<()> {
+        match *self {}
+    }
+
+    pub fn suspend<F: FnOnce() -> R, R>(&self, _f: F) -> R {
+        match *self {}
+    }
+}
+
+/// Byte counts in binary units, formatted like indicatif's `HumanBytes`.
+#[cfg(not(feature = "tui"))]
+pub struct HumanBytes(pub u64);
+
+#[cfg(not(feature = "tui"))]
+impl std::fmt::Display for HumanBytes {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        const UNITS: [&str; 6] = ["KiB", "MiB", "GiB", "TiB", "PiB", "EiB"];
+        if self.0 < 1024 {
+            return write!(f, "{} B", self.0);
+        }
+        let mut value = self.0 as f64 / 1024.0;
+        let mut unit = 0;
+        while value >= 1024.0 && unit < UNITS.len() - 1 {
+            value /= 1024.0;
+            unit += 1;
+        }
+        write!(f, "{:.2} {}", value, UNITS[unit])
+    }
+}
+
+/// Timestamped samples of a download's byte count, for its peak and
+/// average speed.
+#[derive(Debug, Default)]
+pub struct SpeedTracker {
+    first: Option<(Instant, u64)>,
+    last: Option<(Instant, u64)>,
+    window: Option<(Instant, u64)>,
+    peak: u64,
+}
+
+impl SpeedTracker {
+    /// Records the total downloaded so far and returns the bytes added
+    /// since the previous sample. The first sample only sets the baseline,
+    /// since it includes whatever a resumed file already had.
+    pub fn sample(&mut self, downloaded: u64) -> u64 {
+        self.sample_at(Instant::now(), downloaded)
+    }
+
+    fn sample_at(&mut self, now: Instant, downloaded: u64) -> u64 {
+        let delta = self
+            .last
+            .map_or(0, |(_, prev)| downloaded.saturating_sub(prev));
+        self.first.get_or_insert((now, downloaded));
+        self.last = Some((now, downloaded));
+
+        let (start, start_bytes) = *self.window.get_or_insert((now, downloaded));
+        let elapsed = now.duration_since(start);
+        if elapsed >= PEAK_WINDOW {
+            let rate = downloaded.saturating_sub(start_bytes) as f64 / elapsed.as_secs_f64();
+            self.peak = self.peak.max(rate as u64);
+            self.window = Some((now, downloaded));
+        }
+        delta
+    }
+
+    /// Bytes per second between the first and last sample.
+    pub fn average(&self) -> u64 {
+        match (self.first, self.last) {
+            (Some((start, from)), Some((end, to))) if end > start => {
+                (to.saturating_sub(from) as f64 / end.duration_since(start).as_secs_f64()) as u64
+            }
+            _ => 0,
+        }
+    }
+
+    /// Fastest rate over any one-second window, or the average for
+    /// downloads too short to fill one.
+    pub fn peak(&self) -> u64 {
+        self.peak.max(self.average())
+    }
+
+    /// Bytes received between the first and last sample, leaving out
+    /// whatever a resumed file already had.
+    pub fn transferred(&self) -> u64 {
+        match (self.first, self.last) {
+            (Some((_, from)), Some((_, to))) => to.saturating_sub(from),
+            _ => 0,
+        }
+    }
+
+    /// The last reported total.
+    pub fn downloaded(&self) -> u64 {
+        self.last.map_or(0, |(_, bytes)| bytes)
+    }
+}
+
+/// Per-file progress: an indicatif bar on a terminal, periodic plain
+/// text lines when stdout is redirected (e.g. CI logs), or key=value
+/// lines for frontends.
+pub enum Progress {
+    #[cfg(feature = "tui")]
+    Bar(BarProgress),
+    Plain(PlainProgress),
+    Machine(MachineProgress),
+    Callback(CallbackProgress),
+}
+
+/// What a `ProgressCallback` is told on every progress line.
+#[derive(Debug, Clone, Copy)]
+pub struct ProgressUpdate<'a> {
+    pub url: &'a str,
+    pub filename: &'a str,
+    pub downloaded: u64,
+    /// 0 while the size isn't known yet
+    pub total: u64,
+}
+
+/// Progress hook for library users, replacing dlrs's own output.
+///
+/// It's called from the task driving the download, which may move between
+/// runtime threads, and parallel downloads call it concurrently, hence
+/// `Send + Sync`. Calls for one download arrive in order. It runs on the
+/// async runtime, so it should return quickly rather than block.
+pub type ProgressCallback = Arc<dyn Fn(&ProgressUpdate) + Send + Sync>;
+
+pub struct CallbackProgress {
+    url: String,
+    name: String,
+    callback: ProgressCallback,
+}
+
+#[cfg(feature = "tui")]
+pub struct BarProgress {
+    pb: ProgressBar,
+    name: String,
+    connections: Option<u32>,
+}
+
+pub struct MachineProgress {
+    fd: i32,
+    url: String,
+    name: String,
+    last_report: Instant,
+    last_downloaded: u64,
+}
+
+pub struct PlainProgress {
+    name: String,
+    last_report: Instant,
+    last_downloaded: u64,
+}
+
+impl Progress {
+    #[cfg(feature = "tui")]
+    pub fn bar(mp: &MultiProgress, name: &str) -> Result<Self> {
+        let pb = mp.add(ProgressBar::new(0));
+        pb.set_style(
+            ProgressStyle::with_template(
+                "{spinner:.green} [{elapsed_precise:.yellow}] [{bar:40.cyan/blue}] {bytes}/{total_bytes} {binary_bytes_per_sec:.magenta} (ETA: {eta:.blue}) {msg}",
+            )?
+            .progress_chars("=>-"),
+        );
+        pb.set_message(name.to_string());
+        pb.enable_steady_tick(Duration::from_millis(100));
+        Ok(Progress::Bar(BarProgress {
+            pb,
+            name: name.to_string(),
+            connections: None,
+        }))
+    }
+
+    #[cfg(not(feature = "tui"))]
+    pub fn bar(mp: &MultiProgress, _name: &str) -> Result<Self> {
+        match *mp {}
+    }
+
+    pub fn plain(name: &str) -> Self {
+        Progress::Plain(PlainProgress {
+            name: name.to_string(),
+            last_report: Instant::now(),
+            last_downloaded: 0,
+        })
+    }
+
+    pub fn callback(callback: ProgressCallback, url: &str, name: &str) -> Self {
+        Progress::Callback(CallbackProgress {
+            url: url.to_string(),
+            name: name.to_string(),
+            callback,
+        })
+    }
+
+    pub fn machine(fd: i32, url: &str, name: &str) -> Self {
+        let p = MachineProgress {
+            fd,
+            url: url.to_string(),
+            name: name.to_string(),
+            last_report: Instant::now(),
+            last_downloaded: 0,
+        };
+        p.emit("start", &[]);
+        Progress::Machine(p)
+    }
+
+    pub fn update(&mut self, downloaded: u64, total: u64) {
+        match self {
+            Progress::Machine(p) => {
+                let elapsed = p.last_report.elapsed().as_secs_f64();
+                let speed = if elapsed > 0.0 {
+                    (downloaded.saturating_sub(p.last_downloaded) as f64 / elapsed) as u64
+                } else {
+                    0
+                };
+                p.emit(
+                    "progress",
+                    &[
+                        ("done", downloaded.to_string()),
+                        ("total", total.to_string()),
+                        ("speed", speed.to_string()),
+                    ],
+                );
+                p.last_report = Instant::now();
+                p.last_downloaded = downloaded;
+            }
+            #[cfg(feature = "tui")]
+            Progress::Bar(b) => {
+                // aria2c reports a total of 0 while a compressed (or chunked)
+                // response's length is unknown; keep the last known total
+                // instead of resetting the ETA
+                if total > 0 {
+                    b.pb.set_length(total);
+                }
+                b.pb.set_position(downloaded);
+            }
+            Progress::Plain(p) => {
+                let elapsed = p.last_report.elapsed();
+                if elapsed < PLAIN_INTERVAL {
+                    return;
+                }
+                let speed = (downloaded.saturating_sub(p.last_downloaded) as f64
+                    / elapsed.as_secs_f64()) as u64;
+                println!(
+                    "{}",
+                    format_plain_progress(&p.name, downloaded, total, speed)
+                );
+                p.last_report = Instant::now();
+                p.last_downloaded = downloaded;
+            }
+            Progress::Callback(p) => (p.callback)(&ProgressUpdate {
+                url: &p.url,
+                filename: &p.name,
+                downloaded,
+                total,
+            }),
+        }
+    }
+
+    /// Sets the expected size before the first progress line arrives, so the
+    /// bar has a length and ETA right away. Plain and machine output only
+    /// report totals alongside progress, so they wait for aria2c's.
+    pub fn set_total(&mut self, total: u64) {
+        match self {
+            #[cfg(feature = "tui")]
+            Progress::Bar(b) => b.pb.set_length(total),
+            Progress::Machine(_) | Progress::Plain(_) | Progress::Callback(_) => {
+                let _ = total;
+            }
+        }
+    }
+
+    /// Shows how many connections aria2c has open, as `name [CN:16]`, to
+    /// tell whether a split download is really using several.
+    pub fn set_connections(&mut self, connections: u32) {
+        match self {
+            #[cfg(feature = "tui")]
+            Progress::Bar(b) => {
+                if b.connections != Some(connections) {
+                    b.connections = Some(connections);
+                    b.pb.set_message(format!("{} [CN:{}]", b.name, connections));
+                }
+            }
+            Progress::Machine(_) | Progress::Plain(_) | Progress::Callback(_) => {
+                let _ = connections;
+            }
+        }
+    }
+
+    /// Removes the bar without a final line (used on cancellation).
+    pub fn clear(self) {
+        match self {
+            #[cfg(feature = "tui")]
+            Progress::Bar(b) => b.pb.finish_and_clear(),
+            Progress::Machine(p) => p.emit("end", &[("status", "cancelled".to_string())]),
+            Progress::Plain(_) | Progress::Callback(_) => {}
+        }
+    }
+
+    /// Finishes a successful download that turned out to be empty.
+    pub fn finish_empty(self) {
+        match self {
+            #[cfg(feature = "tui")]
+            Progress::Bar(b) => {
+                if let Ok(style) = ProgressStyle::with_template("{msg}") {
+                    b.pb.set_style(style);
+                }
+                b.pb.finish_with_message(format!("✔ {} (0 B)", b.name));
+            }
+            Progress::Plain(p) => println!("{}: done (0 B)", p.name),
+            Progress::Machine(p) => p.emit(
+                "end",
+                &[
+                    ("status", "ok".to_string()),
+                    ("done", "0".to_string()),
+                    ("total", "0".to_string()),
+                ],
+            ),
+            // Library callers learn the outcome from download_file's result
+            Progress::Callback(_) => {}
+        }
+    }
+
+    pub fn finish(self, success: bool, speed: &SpeedTracker) {
+        match self {
+            #[cfg(feature = "tui")]
+            Progress::Bar(b) => {
+                if success {
+                    b.pb.finish_and_clear();
+                } else {
+                    b.pb.finish_with_message(format!("✘ Failed {}", b.name));
+                }
+            }
+            Progress::Plain(p) => {
+                if success {
+                    println!("{}: done", p.name);
+                } else {
+                    println!("{}: failed", p.name);
+                }
+            }
+            Progress::Machine(p) => {
+                let status = if success { "ok" } else { "failed" };
+                p.emit(
+                    "end",
+                    &[
+                        ("status", status.to_string()),
+                        ("avg", speed.average().to_string()),
+                        ("peak", speed.peak().to_string()),
+                    ],
+                );
+            }
+            Progress::Callback(_) => {}
+        }
+    }
+}
+
+impl MachineProgress {
+    fn emit(&self, event: &str, fields: &[(&str, String)]) {
+        if let Some(socket) = SOCKET.get() {
+            socket.send(format_json_line(event, &self.url, &self.name, fields));
+            return;
+        }
+        let line = format_machine_line(event, &self.url, &self.name, fields);
+        write_to_fd(self.fd, line.as_bytes());
+    }
+}
+
+/// Hands `--progress-socket` events to a thread of their own: writing to
+/// the frontend blocks, and the downloads emit them from the async runtime.
+struct SocketWriter {
+    queue: Mutex<Option<SyncSender<String>>>,
+    /// Disconnects once the thread has written everything, or given up
+    done: Mutex<Option<Receiver<()>>>,
+}
+
+impl SocketWriter {
+    fn spawn(mut stream: impl Write + Send + 'static) -> Self {
+        let (queue, events) = std::sync::mpsc::sync_channel::<String>(SOCKET_QUEUE);
+        let (finished, done) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            let _finished = finished;
+            for line in events {
+                // A frontend that went away or stopped reading shouldn't fail
+                // the download; a partly written line means it's out of sync
+                // anyway, so no more are sent
+                if stream.write_all(line.as_bytes()).is_err() {
+                    return;
+                }
+            }
+        });
+        SocketWriter {
+            queue: Mutex::new(Some(queue)),
+            done: Mutex::new(Some(done)),
+        }
+    }
+
+    /// Queues `line`, or drops it when the queue is full or the writer gave up.
+    fn send(&self, line: String) {
+        if let Some(queue) = self.queue.lock().unwrap().as_ref() {
+            let _ = queue.try_send(line);
+        }
+    }
+
+    /// Waits up to `SOCKET_WRITE_TIMEOUT` for the queued events to go out;
+    /// later ones are dropped.
+    fn close(&self) {
+        self.queue.lock().unwrap().take();
+        if let Some(done) = self.done.lock().unwrap().take() {
+            let _ = done.recv_timeout(SOCKET_WRITE_TIMEOUT);
+        }
+    }
+}
+
+/// Connects to the frontend listening on `path` for `--progress-socket`: a
+/// Unix domain socket, or on Windows a named pipe (`\\.\pipe\<name>`).
+pub fn connect_socket(path: &str) -> Result<()> {
+    let stream = open_socket(path)?;
+    let _ = SOCKET.set(SocketWriter::spawn(stream));
+    Ok(())
+}
+
+/// Sends what's left of the `--progress-socket` events before dlrs exits.
+pub fn close_socket() {
+    if let Some(socket) = SOCKET.get() {
+        socket.close();
+    }
+}
+
+#[cfg(unix)]
+fn open_socket(path: &str) -> Result<std::os::unix::net::UnixStream> {
+    use anyhow::Context;
+    let stream = std::os::unix::net::UnixStream::connect(path)
+        .context(format!("Failed to connect to progress socket {}", path))?;
+    stream
+        .set_write_timeout(Some(SOCKET_WRITE_TIMEOUT))
+        .context(format!("Failed to set up progress socket {}", path))?;
+    Ok(stream)
+}
+
+#[cfg(windows)]
+fn open_socket(path: &str) -> Result<std::fs::File> {
+    use anyhow::Context;
+    // The client end of a pipe the frontend created opens like a file
+    std::fs::OpenOptions::new()
+        .write(true)
+        .open(path)
+        .context(format!("Failed to connect to progress pipe {}", path))
+}
+
+#[cfg(not(any(unix, windows)))]
+fn open_socket(_path: &str) -> Result<std::fs::File> {
+    anyhow::bail!(
+        "--progress-socket needs Unix domain sockets or named pipes, which this platform lacks"
+    )
+}
+
+/// Checks `--progress-fd` before anything is written to it. Stdout and
+/// stderr go through their own handles; any other descriptor must be above
+/// stdin and already open.
+pub fn check_progress_fd(fd: i32) -> Result<()> {
+    match fd {
+        1 | 2 => Ok(()),
+        #[cfg(unix)]
+        fd if fd > 2 => {
+            if unsafe { libc::fcntl(fd, libc::F_GETFD) } == -1 {
+                anyhow::bail!("--progress-fd {} isn't an open file descriptor", fd);
+            }
+            Ok(())
+        }
+        _ => anyhow::bail!(
+            "--progress-fd must be 1, 2 or an open descriptor above 2 (Unix only), not {}",
+            fd
+        ),
+    }
+}
+
+/// `<event> url=<url> file=<name> key=value...`, one line per event.
+fn format_machine_line(event: &str, url: &str, name: &str, fields: &[(&str, String)]) -> String {
+    let mut line = format!(
+        "{} url={} file={}",
+        event,
+        utf8_percent_encode(url, MACHINE_ESCAPES),
+        utf8_percent_encode(name, MACHINE_ESCAPES)
+    );
+    for (key, value) in fields {
+        line.push_str(&format!(" {}={}", key, value));
+    }
+    line.push('\n');
+    line
+}
+
+/// The same event as a JSON object: `event`, `url` and `file`, then the
+/// fields, with numeric values as numbers.
+fn format_json_line(event: &str, url: &str, name: &str, fields: &[(&str, String)]) -> String {
+    let string = |s: &str| serde_json::Value::from(s).to_string();
+    let mut line = format!(
+        "{{\"event\":{},\"url\":{},\"file\":{}",
+        string(event),
+        string(url),
+        string(name)
+    );
+    for (key, value) in fields {
+        let value = match value.parse::<u64>() {
+            Ok(n) => n.to_string(),
+            Err(_) => string(value),
+        };
+        line.push_str(&format!(",{}:{}", string(key), value));
+    }
+    line.push_str("}\n");
+    line
+}
+
+/// Writes a whole line in one call, so parallel downloads don't interleave.
+fn write_to_fd(fd: i32, buf: &[u8]) {
+    match fd {
+        1 => {
+            let _ = std::io::stdout().lock().write_all(buf);
+        }
+        2 => {
+            let _ = std::io::stderr().lock().write_all(buf);
+        }
+        #[cfg(unix)]
+        fd if fd > 2 => {
+            use std::os::fd::FromRawFd;
+            // Checked by check_progress_fd at startup. The descriptor belongs
+            // to whoever started dlrs; don't close it
+            let mut file = std::mem::ManuallyDrop::new(unsafe { std::fs::File::from_raw_fd(fd) });
+            let _ = file.write_all(buf);
+        }
+        _ => {}
+    }
+}
+
+/// The "N/M Files" batch bar and the "2 active, 12 done, 36 queued" line
+/// under it, followed by the batch's bytes, rate and (when every size is
+/// known up front) ETA.
+#[cfg(feature = "tui")]
+pub struct BatchStatus {
+    total: AtomicUsize,
+    active: AtomicUsize,
+    done: AtomicUsize,
+    /// Sum of the files' sizes, or `None` if any is unknown
+    expected_bytes: std::sync::Mutex<Option<u64>>,
+    /// Bytes received this run, as last reported by `update_bytes`
+    received: AtomicU64,
+    /// Bytes of finished files that didn't need downloading (skipped,
+    /// resumed), so they don't count as remaining
+    credited: AtomicU64,
+    started: Instant,
+    bar: ProgressBar,
+    line: ProgressBar,
+}
+
+#[cfg(feature = "tui")]
+impl BatchStatus {
+    pub fn new(mp: &MultiProgress, total: usize, expected_bytes: Option<u64>) -> Result<Self> {
+        let bar = mp.add(ProgressBar::new(total as u64));
+        bar.set_style(
+            ProgressStyle::with_template("{bar:40.green/white} {pos}/{len} Files")?
+                .progress_chars("##-"),
+        );
+        bar.enable_steady_tick(Duration::from_millis(100));
+        let line = mp.add(ProgressBar::new_spinner());
+        line.set_style(ProgressStyle::with_template("{msg}")?);
+        let status = BatchStatus {
+            total: AtomicUsize::new(total),
+            active: AtomicUsize::new(0),
+            done: AtomicUsize::new(0),
+            expected_bytes: std::sync::Mutex::new(expected_bytes),
+            received: AtomicU64::new(0),
+            credited: AtomicU64::new(0),
+            started: Instant::now(),
+            bar,
+            line,
+        };
+        status.render();
+        Ok(status)
+    }
+
+    pub fn started(&self) {
+        self.active.fetch_add(1, Ordering::SeqCst);
+        self.render();
+    }
+
+    /// Grows the batch, e.g. by files a `--follow-manifest` manifest listed.
+    pub fn add_total(&self, files: usize) {
+        self.total.fetch_add(files, Ordering::SeqCst);
+        // Their sizes weren't probed, so there's no ETA from here on
+        *self.expected_bytes.lock().unwrap() = None;
+        self.bar.inc_length(files as u64);
+        self.render();
+    }
+
+    /// Marks a file done; `credit` is the part of its size that wasn't
+    /// received this run.
+    pub fn finished(&self, credit: u64) {
+        self.bar.inc(1);
+        self.active.fetch_sub(1, Ordering::SeqCst);
+        self.done.fetch_add(1, Ordering::SeqCst);
+        self.credited.fetch_add(credit, Ordering::SeqCst);
+        self.render();
+    }
+
+    /// Records the bytes received across the batch so far.
+    pub fn update_bytes(&self, received: u64) {
+        self.received.store(received, Ordering::SeqCst);
+        self.render();
+    }
+
+    fn render(&self) {
+        let active = self.active.load(Ordering::SeqCst);
+        let done = self.done.load(Ordering::SeqCst);
+        let queued = self
+            .total
+            .load(Ordering::SeqCst)
+            .saturating_sub(active + done);
+        let mut message = format_batch_status(active, done, queued);
+        let received = self.received.load(Ordering::SeqCst);
+        if received > 0 {
+            let elapsed = self.started.elapsed().as_secs_f64();
+            let rate = if elapsed > 0.0 {
+                (received as f64 / elapsed) as u64
+            } else {
+                0
+            };
+            message.push_str(", ");
+            message.push_str(&format_batch_bytes(
+                received + self.credited.load(Ordering::SeqCst),
+                *self.expected_bytes.lock().unwrap(),
+                rate,
+            ));
+        }
+        self.line.set_message(message);
+    }
+}
+
+/// Builds without the `tui` feature have no batch bar; like `MultiProgress`
+/// this can't be constructed.
+#[cfg(not(feature = "tui"))]
+pub enum BatchStatus {}
+
+#[cfg(not(feature = "tui"))]
+impl BatchStatus {
+    pub fn new(mp: &MultiProgress, _total: usize, _expected_bytes: Option<u64>) -> Result<Self> {
+        match *mp {}
+    }
+
+    pub fn started(&self) {
+        match *self {}
+    }
+
+    pub fn add_total(&self, _files: usize) {
+        match *self {}
+    }
+
+    pub fn finished(&self, _credit: u64) {
+        match *self {}
+    }
+
+    pub fn update_bytes(&self, _received: u64) {
+        match *self {}
+    }
+}
+
+#[cfg(feature = "tui")]
+fn format_batch_status(active: usize, done: usize, queued: usize) -> String {
+    format!("{} active, {} done, {} queued", active, done, queued)
+}
+
+/// `1.00 GiB/4.00 GiB at 10.00 MiB/s, ETA 5m 07s`, or just the bytes and
+/// rate when the batch's size isn't known.
+#[cfg(feature = "tui")]
+fn format_batch_bytes(done: u64, expected: Option<u64>, bytes_per_sec: u64) -> String {
+    let Some(expected) = expected else {
+        return format!("{} at {}/s", HumanBytes(done), HumanBytes(bytes_per_sec));
+    };
+    let eta = match expected.saturating_sub(done).checked_div(bytes_per_sec) {
+        Some(secs) => format_eta(secs),
+        None => "?".to_string(),
+    };
+    format!(
+        "{}/{} at {}/s, ETA {}",
+        HumanBytes(done.min(expected)),
+        HumanBytes(expected),
+        HumanBytes(bytes_per_sec),
+        eta
+    )
+}
+
+#[cfg(feature = "tui")]
+fn format_eta(secs: u64) -> String {
+    match secs {
+        0..60 => format!("{}s", secs),
+        60..3600 => format!("{}m {:02}s", secs / 60, secs % 60),
+        _ => format!("{}h {:02}m", secs / 3600, secs % 3600 / 60),
+    }
+}
+
+fn format_plain_progress(name: &str, downloaded: u64, total: u64, bytes_per_sec: u64) -> String {
+    let percent = (downloaded * 100).checked_div(total).unwrap_or(0);
+    format!(
+        "{}: {}% {}/{} {}/s",
+        name,
+        percent,
+        HumanBytes(downloaded),
+        HumanBytes(total),
+        HumanBytes(bytes_per_sec)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_progress_fd() {
+        assert!(check_progress_fd(2).is_ok());
+        assert!(check_progress_fd(0).is_err());
+        assert!(check_progress_fd(-1).is_err());
+        assert!(check_progress_fd(9999).is_err());
+    }
+
+    #[cfg(feature = "tui")]
+    #[test]
+    fn test_format_batch_status() {
+        assert_eq!(
+            format_batch_status(2, 12, 36),
+            "2 active, 12 done, 36 queued"
+        );
+    }
+
+    #[cfg(feature = "tui")]
+    #[test]
+    fn test_format_batch_bytes() {
+        const MIB: u64 = 1024 * 1024;
+        assert_eq!(
+            format_batch_bytes(100 * MIB, Some(400 * MIB), MIB),
+            "100.00 MiB/400.00 MiB at 1.00 MiB/s, ETA 5m 00s"
+        );
+        assert_eq!(
+            format_batch_bytes(100 * MIB, None, MIB),
+            "100.00 MiB at 1.00 MiB/s"
+        );
+        assert_eq!(
+            format_batch_bytes(0, Some(MIB), 0),
+            "0 B/1.00 MiB at 0 B/s, ETA ?"
+        );
+        assert_eq!(format_eta(42), "42s");
+        assert_eq!(format_eta(7260), "2h 01m");
+    }
+
+    #[test]
+    fn test_speed_tracker() {
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        let mut speed = SpeedTracker::default();
+
+        // A resumed download starts from what's already on disk
+        assert_eq!(speed.sample_at(at(0), 1000), 0);
+        assert_eq!(speed.sample_at(at(500), 2000), 1000);
+        assert_eq!(speed.sample_at(at(1000), 5000), 3000);
+        assert_eq!(speed.sample_at(at(2000), 6000), 1000);
+        assert_eq!(speed.sample_at(at(4000), 7000), 1000);
+
+        assert_eq!(speed.downloaded(), 7000);
+        assert_eq!(speed.transferred(), 6000);
+        assert_eq!(speed.average(), 1500);
+        assert_eq!(speed.peak(), 4000);
+    }
+
+    #[test]
+    fn test_format_machine_line() {
+        assert_eq!(
+            format_machine_line(
+                "progress",
+                "https://example.com/a b.zip?x=1",
+                "my file.zip",
+                &[("done", "123".to_string()), ("total", "456".to_string())]
+            ),
+            "progress url=https://example.com/a%20b.zip?x%3D1 file=my%20file.zip done=123 total=456\n"
+        );
+    }
+
+    #[test]
+    fn test_format_json_line() {
+        assert_eq!(
+            format_json_line(
+                "end",
+                "https://example.com/a b.zip",
+                "a b.zip",
+                &[("status", "ok".to_string()), ("avg", "2048".to_string())]
+            ),
+            "{\"event\":\"end\",\"url\":\"https://example.com/a b.zip\",\"file\":\"a b.zip\",\"status\":\"ok\",\"avg\":2048}\n"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_socket_writer() {
+        use std::io::{BufRead, BufReader};
+        use std::os::unix::net::UnixStream;
+
+        let (stream, frontend) = UnixStream::pair().unwrap();
+        let writer = SocketWriter::spawn(stream);
+        writer.send("{\"event\":\"start\"}\n".to_string());
+        writer.send("{\"event\":\"end\"}\n".to_string());
+        writer.close();
+        // Closed: nothing more is queued
+        writer.send("{\"event\":\"late\"}\n".to_string());
+        let lines: Vec<String> = BufReader::new(frontend)
+            .lines()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(lines, ["{\"event\":\"start\"}", "{\"event\":\"end\"}"]);
+
+        // A frontend that never reads can't hold up the sender
+        let (stream, _frontend) = UnixStream::pair().unwrap();
+        stream
+            .set_write_timeout(Some(SOCKET_WRITE_TIMEOUT))
+            .unwrap();
+        let writer = SocketWriter::spawn(stream);
+        let started = Instant::now();
+        for _ in 0..SOCKET_QUEUE * 4 {
+            writer.send(format!("{}\n", "x".repeat(1024)));
+        }
+        assert!(started.elapsed() < SOCKET_WRITE_TIMEOUT);
+        writer.close();
+        assert!(started.elapsed() < SOCKET_WRITE_TIMEOUT * 3);
+    }
+
+    #[test]
+    fn test_format_plain_progress() {
+        assert_eq!(
+            format_plain_progress("file.zip", 512 * 1024, 1024 * 1024, 2048),
+            "file.zip: 50% 512.00 KiB/1.00 MiB 2.00 KiB/s"
+        );
+        assert_eq!(
+            format_plain_progress("empty", 0, 0, 0),
+            "empty: 0% 0 B/0 B 0 B/s"
+        );
+    }
+
+    #[cfg(feature = "tui")]
+    #[test]
+    fn test_set_total() {
+        let mp = MultiProgress::with_draw_target(indicatif::ProgressDrawTarget::hidden());
+        let mut progress = Progress::bar(&mp, "a.iso").unwrap();
+        progress.set_total(4096);
+        // aria2c's early lines carry no total yet; the probed one stays
+        progress.update(0, 0);
+        let Progress::Bar(b) = &progress else {
+            unreachable!()
+        };
+        assert_eq!(b.pb.length(), Some(4096));
+        progress.update(1024, 8192);
+        let Progress::Bar(b) = &progress else {
+            unreachable!()
+        };
+        assert_eq!(b.pb.length(), Some(8192));
+    }
+
+    #[cfg(feature = "tui")]
+    #[test]
+    fn test_set_connections() {
+        let mp = MultiProgress::with_draw_target(indicatif::ProgressDrawTarget::hidden());
+        let mut progress = Progress::bar(&mp, "file.zip").unwrap();
+        progress.set_connections(16);
+        let Progress::Bar(b) = &progress else {
+            unreachable!()
+        };
+        assert_eq!(b.pb.message(), "file.zip [CN:16]");
+    }
+}
@@ -0,0 +1,98 @@
+use crate::utils::normalize_size;
+use anyhow::{Context, Result};
+use chrono::NaiveTime;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpeedWindow {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+    pub limit: String,
+}
+
+impl SpeedWindow {
+    fn contains(&self, time: NaiveTime) -> bool {
+        if self.start <= self.end {
+            time >= self.start && time < self.end
+        } else {
+            // Window wraps around midnight (e.g. 22:00-06:00)
+            time >= self.start || time < self.end
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpeedSchedule {
+    windows: Vec<SpeedWindow>,
+}
+
+impl SpeedSchedule {
+    /// Returns the limit of the first window containing `time`.
+    pub fn limit_at(&self, time: NaiveTime) -> Option<&str> {
+        self.windows
+            .iter()
+            .find(|w| w.contains(time))
+            .map(|w| w.limit.as_str())
+    }
+}
+
+/// Parses a schedule such as `09:00-18:00=1M, 18:00-09:00=0`.
+///
+/// A limit of `0` means unthrottled, matching aria2c's `--max-download-limit`.
+pub fn parse_speed_schedule(spec: &str) -> Result<SpeedSchedule> {
+    let mut windows = Vec::new();
+
+    for entry in spec.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+        let (range, limit) = entry
+            .split_once('=')
+            .context(format!("Missing '=<limit>' in schedule entry '{}'", entry))?;
+        let (start, end) = range.split_once('-').context(format!(
+            "Expected HH:MM-HH:MM in schedule entry '{}'",
+            entry
+        ))?;
+
+        let start = NaiveTime::parse_from_str(start.trim(), "%H:%M")
+            .context(format!("Invalid start time in schedule entry '{}'", entry))?;
+        let end = NaiveTime::parse_from_str(end.trim(), "%H:%M")
+            .context(format!("Invalid end time in schedule entry '{}'", entry))?;
+        let limit = limit.trim();
+        if limit.is_empty() {
+            anyhow::bail!("Empty limit in schedule entry '{}'", entry);
+        }
+        let limit = normalize_size(limit)
+            .context(format!("Invalid limit in schedule entry '{}'", entry))?;
+        if start == end {
+            anyhow::bail!("Schedule window '{}' has zero length", entry);
+        }
+
+        windows.push(SpeedWindow { start, end, limit });
+    }
+
+    if windows.is_empty() {
+        anyhow::bail!("Speed schedule must contain at least one window");
+    }
+
+    Ok(SpeedSchedule { windows })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn t(h: u32, m: u32) -> NaiveTime {
+        NaiveTime::from_hms_opt(h, m, 0).unwrap()
+    }
+
+    #[test]
+    fn test_parse_speed_schedule() {
+        let s = parse_speed_schedule("09:00-18:00=1M, 22:00-06:00=0").unwrap();
+        assert_eq!(s.limit_at(t(12, 0)), Some("1M"));
+        assert_eq!(s.limit_at(t(18, 0)), None);
+        assert_eq!(s.limit_at(t(23, 30)), Some("0"));
+        assert_eq!(s.limit_at(t(5, 59)), Some("0"));
+
+        assert!(parse_speed_schedule("").is_err());
+        assert!(parse_speed_schedule("09:00-18:00").is_err());
+        assert!(parse_speed_schedule("9am-6pm=1M").is_err());
+        assert!(parse_speed_schedule("09:00-09:00=1M").is_err());
+    }
+}
@@ -0,0 +1,246 @@
+use crate::cli::GetArgs;
+use crate::engine::{DownloadItem, has_partial_download};
+use crate::input::parse_input_file;
+use crate::lock::DownloadLock;
+use anyhow::{Context, Result};
+use std::io::Write;
+use std::path::Path;
+use std::sync::Mutex;
+
+const SESSION_FILE: &str = ".dlrs-session";
+const SESSION_HEADER: &str = "# Unfinished dlrs downloads, for `dlrs --resume-all`\n";
+
+/// Headers that carry credentials; they're left out of the session file,
+/// so pass them again (e.g. through `DLRS_HEADER_*`) when resuming.
+const SECRET_HEADERS: &[&str] = &["authorization", "proxy-authorization", "cookie"];
+
+/// Serializes read-modify-write cycles of session files within this
+/// process; the file's lock does the same across processes, but `flock`
+/// locks held by one process don't exclude each other.
+static SESSION_LOCK: Mutex<()> = Mutex::new(());
+
+/// Notes `item`, downloading into `item_dir`, as unfinished in
+/// `.dlrs-session` in `target_dir`, an input file listing the downloads
+/// that have a partial file to resume. aria2c's control files don't keep
+/// the URL, so this is how `--resume-all` finds it.
+pub fn record(target_dir: &str, item_dir: &str, item: &DownloadItem) -> Result<()> {
+    update(target_dir, |entries| {
+        entries.retain(|e| e.url != item.url);
+        let mut entry = DownloadItem::new(item.url.clone());
+        // The directories of a --mirror-structure name come back from the URL
+        let name = item.filename.rsplit('/').next().unwrap_or(&item.filename);
+        entry.options.out = Some(name.to_string());
+        // A --dest-template directory may not come out the same next time
+        if item_dir != target_dir {
+            entry.options.dir = Some(item_dir.to_string());
+        }
+        entry.options.headers = item
+            .options
+            .headers
+            .iter()
+            .filter(|h| {
+                let name = h.split(':').next().unwrap_or("").trim();
+                !SECRET_HEADERS.contains(&name.to_lowercase().as_str())
+            })
+            .cloned()
+            .collect();
+        entry.options.checksum = item.options.checksum.clone();
+        entry.options.max_speed = item.options.max_speed.clone();
+        entry.options.mirrors = item
+            .options
+            .mirrors
+            .iter()
+            .filter(|m| **m != item.url)
+            .cloned()
+            .collect();
+        entries.push(entry);
+    })
+}
+
+/// Drops the entries for `urls` once their download is complete; the file
+/// goes away with its last entry.
+pub fn forget<'a>(target_dir: &str, urls: impl IntoIterator<Item = &'a String>) -> Result<()> {
+    let urls: Vec<&String> = urls.into_iter().collect();
+    update(target_dir, |entries| {
+        entries.retain(|e| !urls.contains(&&e.url));
+    })
+}
+
+/// The recorded downloads in `dir` that still have something to resume, and
+/// the control files no entry accounts for. Entries whose partial file is
+/// gone are dropped from the session.
+pub fn unfinished(dir: &str, config: &GetArgs) -> Result<(Vec<DownloadItem>, Vec<String>)> {
+    let mut found = Vec::new();
+    update(dir, |entries| {
+        entries.retain(|e| has_partial_download(e, dir, config));
+        found = entries
+            .iter()
+            .map(|e| {
+                let mut item = DownloadItem::new(e.url.clone());
+                item.options = e.options.clone();
+                item
+            })
+            .collect();
+    })?;
+
+    let names: Vec<&str> = found
+        .iter()
+        .filter_map(|i| i.options.out.as_deref())
+        .collect();
+    let work_dir = config.temp_dir.as_deref().unwrap_or(dir);
+    let mut orphans = Vec::new();
+    let listing =
+        std::fs::read_dir(work_dir).context(format!("Failed to read directory '{}'", work_dir))?;
+    for entry in listing.flatten() {
+        let file_name = entry.file_name().to_string_lossy().to_string();
+        let Some(name) = file_name.strip_suffix(".aria2") else {
+            continue;
+        };
+        let name = name.strip_suffix(".dlrs-part").unwrap_or(name);
+        if !names.contains(&name) {
+            orphans.push(file_name);
+        }
+    }
+    orphans.sort();
+    Ok((found, orphans))
+}
+
+/// Applies `change` to the entries in `dir`'s session file and writes them
+/// back, removing the file when none are left. The new contents replace the
+/// file in one rename, so a crash or a reader never sees half of it.
+fn update(dir: &str, change: impl FnOnce(&mut Vec<DownloadItem>)) -> Result<()> {
+    let _guard = SESSION_LOCK.lock().unwrap();
+    let path = Path::new(dir).join(SESSION_FILE);
+    let _lock = DownloadLock::acquire_blocking(&path.to_string_lossy())?;
+    let mut entries = match std::fs::read_to_string(&path) {
+        Ok(contents) => parse_input_file(&contents)
+            .context(format!("Invalid session file '{}'", path.display()))?,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+        Err(e) => {
+            return Err(e).context(format!("Failed to read session file '{}'", path.display()));
+        }
+    };
+    let before = format_session(&entries);
+    change(&mut entries);
+    let after = format_session(&entries);
+    if after == before {
+        return Ok(());
+    }
+    let written = if entries.is_empty() {
+        std::fs::remove_file(&path)
+    } else {
+        write_private(&path, &after)
+    };
+    written.context(format!("Failed to write session file '{}'", path.display()))
+}
+
+/// Writes `contents` to a temporary file readable only by the owner (URLs
+/// can carry tokens too) and renames it over `path`.
+fn write_private(path: &Path, contents: &str) -> std::io::Result<()> {
+    let tmp = path.with_extension("tmp");
+    // A leftover from a crash would keep its old permissions
+    let _ = std::fs::remove_file(&tmp);
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let result = options
+        .open(&tmp)
+        .and_then(|mut file| file.write_all(contents.as_bytes()))
+        .and_then(|()| std::fs::rename(&tmp, path));
+    if result.is_err() {
+        let _ = std::fs::remove_file(&tmp);
+    }
+    result
+}
+
+/// `entries` in the `--input-file` format.
+fn format_session(entries: &[DownloadItem]) -> String {
+    let mut out = String::from(SESSION_HEADER);
+    for entry in entries {
+        out.push_str(&entry.url);
+        out.push('\n');
+        let options = &entry.options;
+        let lines = options
+            .out
+            .iter()
+            .map(|v| ("out", v))
+            .chain(options.dir.iter().map(|v| ("dir", v)))
+            .chain(options.headers.iter().map(|v| ("header", v)))
+            .chain(options.checksum.iter().map(|v| ("checksum", v)))
+            .chain(options.max_speed.iter().map(|v| ("max-speed", v)))
+            .chain(options.mirrors.iter().map(|v| ("mirror", v)));
+        for (key, value) in lines {
+            out.push_str(&format!("  {}={}\n", key, value));
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::test_dir;
+    use clap::Parser;
+
+    #[test]
+    fn test_session() {
+        let dir = test_dir("session");
+        let target = dir.path_str();
+        let config = GetArgs::parse_from(["dlrs"]);
+
+        let mut a = DownloadItem::new("https://example.com/a.iso".to_string());
+        a.filename = "a.iso".to_string();
+        a.options.checksum = Some("sha-256=abc".to_string());
+        a.options.mirrors = vec!["https://mirror.example.com/a.iso".to_string()];
+        a.options.headers = vec![
+            "Authorization: Bearer t".to_string(),
+            "X-Team: data".to_string(),
+        ];
+        let mut b = DownloadItem::new("https://example.com/get?id=2".to_string());
+        b.filename = "b.1.bin".to_string();
+        let mut c = DownloadItem::new("https://example.com/c.mp4".to_string());
+        c.filename = "c.mp4".to_string();
+        let routed = dir.join("video").to_string_lossy().to_string();
+        record(&target, &target, &a).unwrap();
+        record(&target, &target, &b).unwrap();
+        record(&target, &routed, &c).unwrap();
+        record(&target, &target, &a).unwrap();
+        let session = std::fs::read_to_string(dir.join(SESSION_FILE)).unwrap();
+        assert_eq!(session.matches("https://example.com/a.iso\n").count(), 1);
+        assert!(session.contains("header=X-Team: data") && !session.contains("Bearer"));
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(dir.join(SESSION_FILE))
+                .unwrap()
+                .permissions()
+                .mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        // a and c (in its own directory) have a partial file; b's entry is
+        // stale and the control file for d was left by a run before sessions
+        // were kept
+        std::fs::create_dir_all(&routed).unwrap();
+        std::fs::write(dir.join("a.iso.aria2"), b"").unwrap();
+        std::fs::write(dir.join("video/c.mp4.aria2"), b"").unwrap();
+        std::fs::write(dir.join("d.zip.dlrs-part.aria2"), b"").unwrap();
+        let (items, orphans) = unfinished(&target, &config).unwrap();
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].url, c.url);
+        assert_eq!(items[0].options.dir.as_deref(), Some(routed.as_str()));
+        assert_eq!(items[1].url, a.url);
+        assert_eq!(items[1].options.out.as_deref(), Some("a.iso"));
+        assert_eq!(items[1].options.dir, None);
+        assert_eq!(items[1].options.checksum, a.options.checksum);
+        assert_eq!(items[1].options.mirrors, a.options.mirrors);
+        assert_eq!(orphans, vec!["d.zip.dlrs-part.aria2"]);
+
+        forget(&target, [&a.url, &c.url]).unwrap();
+        assert!(!dir.join(SESSION_FILE).exists());
+    }
+}
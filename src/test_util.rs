@@ -0,0 +1,35 @@
+use std::path::{Path, PathBuf};
+
+/// A scratch directory for one test: created empty, and removed when it
+/// goes out of scope, failed assertions included.
+pub struct TestDir(PathBuf);
+
+/// `dlrs-<name>-<pid>` under the system temp dir. Tests run in parallel in
+/// one process, so `name` must be unique to the test.
+pub fn test_dir(name: &str) -> TestDir {
+    let dir = std::env::temp_dir().join(format!("dlrs-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    TestDir(dir)
+}
+
+impl TestDir {
+    /// The path as a `String`, for the functions that take directories as `&str`.
+    pub fn path_str(&self) -> String {
+        self.0.to_string_lossy().to_string()
+    }
+}
+
+impl std::ops::Deref for TestDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TestDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
@@ -0,0 +1,114 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Most downloads `--auto-parallel` will run at once.
+pub const AUTO_PARALLEL_MAX: usize = 16;
+
+/// A window must beat the best rate so far by this much to count as an
+/// improvement; anything closer is a plateau.
+const IMPROVEMENT: f64 = 1.1;
+
+/// Bytes received across all downloads: since the last `take`, and in all.
+#[derive(Default)]
+pub struct Throughput {
+    bytes: AtomicU64,
+    total: AtomicU64,
+}
+
+impl Throughput {
+    pub fn add(&self, bytes: u64) {
+        self.bytes.fetch_add(bytes, Ordering::Relaxed);
+        self.total.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    pub fn take(&self) -> u64 {
+        self.bytes.swap(0, Ordering::Relaxed)
+    }
+
+    pub fn total(&self) -> u64 {
+        self.total.load(Ordering::Relaxed)
+    }
+}
+
+/// Hill-climbing concurrency controller for `--auto-parallel`: starts at one
+/// download, adds another while each step raises aggregate throughput, and
+/// steps back when a step didn't help or downloads start failing.
+pub struct Tuner {
+    limit: usize,
+    max: usize,
+    best_rate: f64,
+    stepped_up: bool,
+}
+
+impl Tuner {
+    pub fn new(max: usize) -> Self {
+        Tuner {
+            limit: 1,
+            max: max.max(1),
+            best_rate: 0.0,
+            stepped_up: false,
+        }
+    }
+
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+
+    /// Feeds in one measurement window (bytes per second and downloads
+    /// failed during it) and returns the new concurrency limit.
+    pub fn adjust(&mut self, rate: f64, failures: usize) -> usize {
+        if failures > 0 {
+            self.limit = self.limit.saturating_sub(1).max(1);
+            self.best_rate = rate;
+            self.stepped_up = false;
+        } else if rate > self.best_rate * IMPROVEMENT {
+            self.best_rate = rate;
+            self.stepped_up = self.limit < self.max;
+            self.limit = (self.limit + 1).min(self.max);
+        } else if self.stepped_up {
+            // The last extra download bought nothing; give it back and hold
+            self.limit = self.limit.saturating_sub(1).max(1);
+            self.stepped_up = false;
+        } else if rate * IMPROVEMENT < self.best_rate {
+            // Conditions changed (other traffic, a slower mirror); measure
+            // improvements against the new baseline
+            self.best_rate = rate;
+        }
+        self.limit
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tuner() {
+        let mut tuner = Tuner::new(4);
+        assert_eq!(tuner.limit(), 1);
+
+        // Climbs while throughput improves, capped at the maximum
+        assert_eq!(tuner.adjust(100.0, 0), 2);
+        assert_eq!(tuner.adjust(200.0, 0), 3);
+        // A step that didn't help is undone, then held
+        assert_eq!(tuner.adjust(205.0, 0), 2);
+        assert_eq!(tuner.adjust(200.0, 0), 2);
+        // Failures back off
+        assert_eq!(tuner.adjust(200.0, 1), 1);
+        assert_eq!(tuner.adjust(100.0, 1), 1);
+
+        let mut tuner = Tuner::new(2);
+        tuner.adjust(100.0, 0);
+        assert_eq!(tuner.adjust(300.0, 0), 2);
+    }
+
+    #[test]
+    fn test_throughput() {
+        let throughput = Throughput::default();
+        throughput.add(10);
+        throughput.add(5);
+        assert_eq!(throughput.take(), 15);
+        assert_eq!(throughput.take(), 0);
+        throughput.add(1);
+        assert_eq!(throughput.total(), 16);
+    }
+}
@@ -0,0 +1,64 @@
+use anyhow::{Context, Result};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Location of the usage file: `$XDG_STATE_HOME/dlrs/usage.json`, else
+/// `~/.local/state/dlrs/usage.json`.
+fn usage_path() -> Option<PathBuf> {
+    let base = std::env::var_os("XDG_STATE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|h| PathBuf::from(h).join(".local/state")))?;
+    Some(base.join("dlrs").join("usage.json"))
+}
+
+/// Adds `bytes` to this month's running total for `--track-usage` and
+/// returns the new total.
+pub fn record_usage(bytes: u64) -> Result<u64> {
+    let path = usage_path().context("Can't locate the usage file: $HOME is not set")?;
+    let month = chrono::Local::now().format("%Y-%m").to_string();
+    record_usage_at(&path, &month, bytes)
+}
+
+/// The file maps `YYYY-MM` to bytes, so past months stay around for reference.
+fn record_usage_at(path: &Path, month: &str, bytes: u64) -> Result<u64> {
+    let mut totals: BTreeMap<String, u64> = match std::fs::read_to_string(path) {
+        Ok(contents) => serde_json::from_str(&contents)
+            .context(format!("Invalid usage file '{}'", path.display()))?,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+        Err(e) => {
+            return Err(e).context(format!("Failed to read usage file '{}'", path.display()));
+        }
+    };
+
+    let total = totals.entry(month.to_string()).or_default();
+    *total += bytes;
+    let total = *total;
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).context(format!("Failed to create {:?}", parent))?;
+    }
+    std::fs::write(path, serde_json::to_string_pretty(&totals)?)
+        .context(format!("Failed to write usage file '{}'", path.display()))?;
+    Ok(total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::test_dir;
+
+    #[test]
+    fn test_record_usage() {
+        let dir = test_dir("usage");
+        let path = dir.join("state").join("usage.json");
+
+        assert_eq!(record_usage_at(&path, "2026-09", 100).unwrap(), 100);
+        assert_eq!(record_usage_at(&path, "2026-10", 50).unwrap(), 50);
+        assert_eq!(record_usage_at(&path, "2026-10", 25).unwrap(), 75);
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        let totals: BTreeMap<String, u64> = serde_json::from_str(&contents).unwrap();
+        assert_eq!(totals["2026-09"], 100);
+        assert_eq!(totals["2026-10"], 75);
+    }
+}
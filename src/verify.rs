@@ -0,0 +1,264 @@
+use crate::engine::ensure_within_target;
+use crate::error::DlrsError;
+use crate::logging::{Color, paint};
+use anyhow::{Context, Result};
+use sha2::{Digest, Sha256, Sha512};
+use std::io::Read;
+use std::path::Path;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ManifestEntry {
+    pub digest: String,
+    pub filename: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Verdict {
+    Ok,
+    Failed,
+    Missing,
+}
+
+/// Parses a `sha256sum`-style manifest: `<hex digest>  <filename>` per line,
+/// with an optional `*` before the name for binary mode.
+pub fn parse_manifest(contents: &str) -> Result<Vec<ManifestEntry>> {
+    let mut entries = Vec::new();
+
+    for (idx, line) in contents.lines().enumerate() {
+        let line = line.trim_end();
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let (digest, filename) = line
+            .split_once(' ')
+            .context(format!("Line {}: expected '<digest>  <filename>'", idx + 1))?;
+        let filename = filename.trim_start_matches(' ').trim_start_matches('*');
+        if filename.is_empty() {
+            anyhow::bail!("Line {}: missing filename", idx + 1);
+        }
+        if !digest.chars().all(|c| c.is_ascii_hexdigit()) || digest_len_unsupported(digest) {
+            anyhow::bail!(
+                "Line {}: expected a SHA-256 or SHA-512 hex digest, got '{}'",
+                idx + 1,
+                digest
+            );
+        }
+
+        entries.push(ManifestEntry {
+            digest: digest.to_ascii_lowercase(),
+            filename: filename.to_string(),
+        });
+    }
+
+    Ok(entries)
+}
+
+fn digest_len_unsupported(digest: &str) -> bool {
+    digest.len() != 64 && digest.len() != 128
+}
+
+/// Hex digest of a file, using SHA-512 when `len` is 128 and SHA-256 otherwise.
+fn file_digest(path: &Path, len: usize) -> Result<String> {
+    let mut file = std::fs::File::open(path).context(format!("Failed to open {:?}", path))?;
+    let mut buf = vec![0u8; 64 * 1024];
+    let mut sha256 = Sha256::new();
+    let mut sha512 = Sha512::new();
+
+    loop {
+        let n = file
+            .read(&mut buf)
+            .context(format!("Failed to read {:?}", path))?;
+        if n == 0 {
+            break;
+        }
+        if len == 128 {
+            sha512.update(&buf[..n]);
+        } else {
+            sha256.update(&buf[..n]);
+        }
+    }
+
+    let digest = if len == 128 {
+        sha512.finalize().to_vec()
+    } else {
+        sha256.finalize().to_vec()
+    };
+    Ok(digest.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+/// Hex digest of the file at `path` for an item's `<type>=<digest>`
+/// checksum. Only SHA-256 and SHA-512 are computed here; other types give
+/// `None`.
+pub fn checksum_digest(path: &Path, checksum: &str) -> Option<Result<String>> {
+    let (kind, _) = checksum.split_once('=')?;
+    let len = match kind.to_ascii_lowercase().as_str() {
+        "sha-256" => 64,
+        "sha-512" => 128,
+        _ => return None,
+    };
+    Some(file_digest(path, len))
+}
+
+/// An incremental hash for an item's SHA-256 or SHA-512 checksum, for bytes
+/// that can't be read back afterwards (a FIFO).
+pub enum StreamDigest {
+    Sha256(Sha256),
+    Sha512(Sha512),
+}
+
+impl StreamDigest {
+    /// `None` for checksum types dlrs doesn't compute.
+    pub fn for_checksum(checksum: &str) -> Option<Self> {
+        let (kind, _) = checksum.split_once('=')?;
+        match kind.to_ascii_lowercase().as_str() {
+            "sha-256" => Some(StreamDigest::Sha256(Sha256::new())),
+            "sha-512" => Some(StreamDigest::Sha512(Sha512::new())),
+            _ => None,
+        }
+    }
+
+    pub fn update(&mut self, bytes: &[u8]) {
+        match self {
+            StreamDigest::Sha256(h) => h.update(bytes),
+            StreamDigest::Sha512(h) => h.update(bytes),
+        }
+    }
+
+    pub fn hex(self) -> String {
+        let digest = match self {
+            StreamDigest::Sha256(h) => h.finalize().to_vec(),
+            StreamDigest::Sha512(h) => h.finalize().to_vec(),
+        };
+        digest.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+}
+
+/// Whether hex `digest` is the one `<type>=<digest>` expects.
+pub fn checksum_matches(checksum: &str, digest: &str) -> bool {
+    let expected = checksum.split_once('=').map_or("", |(_, d)| d);
+    expected.eq_ignore_ascii_case(digest)
+}
+
+/// Checks one file against its digest. A name that leads out of `dir` (a
+/// `..` or an absolute path in a downloaded manifest) is an error.
+pub fn verify_entry(dir: &Path, entry: &ManifestEntry) -> Result<Verdict> {
+    let path = dir.join(&entry.filename);
+    ensure_within_target(&dir.to_string_lossy(), &path.to_string_lossy())?;
+    if !path.is_file() {
+        return Ok(Verdict::Missing);
+    }
+    if file_digest(&path, entry.digest.len())? == entry.digest {
+        Ok(Verdict::Ok)
+    } else {
+        Ok(Verdict::Failed)
+    }
+}
+
+/// Checks the files listed in `manifest` (or only `only`, when non-empty)
+/// inside `dir`, printing one `name: OK|FAILED|MISSING` line per file.
+pub fn verify_manifest(manifest: &str, dir: &Path, only: &[String], quiet: bool) -> Result<()> {
+    let contents = std::fs::read_to_string(manifest)
+        .context(format!("Failed to read checksums file '{}'", manifest))?;
+    let entries =
+        parse_manifest(&contents).context(format!("Invalid checksums file '{}'", manifest))?;
+
+    for name in only {
+        if !entries.iter().any(|e| &e.filename == name) {
+            anyhow::bail!("'{}' is not listed in '{}'", name, manifest);
+        }
+    }
+
+    let (mut failed, mut missing) = (0, 0);
+    for entry in entries
+        .iter()
+        .filter(|e| only.is_empty() || only.contains(&e.filename))
+    {
+        let verdict = verify_entry(dir, entry)?;
+        let label = match verdict {
+            Verdict::Ok => paint("OK", Color::Green),
+            Verdict::Failed => {
+                failed += 1;
+                paint("FAILED", Color::Red)
+            }
+            Verdict::Missing => {
+                missing += 1;
+                paint("MISSING", Color::Yellow)
+            }
+        };
+        if !quiet || verdict != Verdict::Ok {
+            println!("{}: {}", entry.filename, label);
+        }
+    }
+
+    if failed > 0 || missing > 0 {
+        return Err(DlrsError::VerifyFailed { failed, missing }.into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::test_dir;
+
+    const HELLO_SHA256: &str = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+
+    #[test]
+    fn test_parse_manifest() {
+        let contents = format!(
+            "{}  hello.txt\n{} *bin/app.tar.gz\n\n# comment\n",
+            HELLO_SHA256.to_uppercase(),
+            "0".repeat(128)
+        );
+        let entries = parse_manifest(&contents).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].digest, HELLO_SHA256);
+        assert_eq!(entries[0].filename, "hello.txt");
+        assert_eq!(entries[1].filename, "bin/app.tar.gz");
+
+        assert!(parse_manifest("abc  file").is_err());
+        assert!(parse_manifest(HELLO_SHA256).is_err());
+        assert!(parse_manifest(&format!("{}  ", HELLO_SHA256)).is_err());
+    }
+
+    #[test]
+    fn test_verify_entry() {
+        let dir = test_dir("verify");
+        std::fs::write(dir.join("hello.txt"), b"hello").unwrap();
+        std::fs::write(dir.join("other.txt"), b"other").unwrap();
+
+        let entry = |name: &str| ManifestEntry {
+            digest: HELLO_SHA256.to_string(),
+            filename: name.to_string(),
+        };
+        assert_eq!(
+            verify_entry(&dir, &entry("hello.txt")).unwrap(),
+            Verdict::Ok
+        );
+        assert_eq!(
+            verify_entry(&dir, &entry("other.txt")).unwrap(),
+            Verdict::Failed
+        );
+        assert_eq!(
+            verify_entry(&dir, &entry("gone.txt")).unwrap(),
+            Verdict::Missing
+        );
+        assert!(verify_entry(&dir, &entry("../hello.txt")).is_err());
+        assert!(verify_entry(&dir, &entry("/etc/hostname")).is_err());
+
+        let hello = dir.join("hello.txt");
+        assert_eq!(
+            checksum_digest(&hello, "SHA-256=abc").unwrap().unwrap(),
+            HELLO_SHA256
+        );
+        assert_eq!(
+            checksum_digest(&hello, "sha-512=abc")
+                .unwrap()
+                .unwrap()
+                .len(),
+            128
+        );
+        assert!(checksum_digest(&hello, "md5=abc").is_none());
+    }
+}